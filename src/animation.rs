@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use nalgebra::Vector2;
+use serde::de::Error;
+
+/// How an animation carries on once it reaches its last frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Playback {
+    /// Starts again from the first frame.
+    #[default]
+    Loop,
+    /// Stays on the last frame.
+    Once,
+    /// Plays backwards to the first frame, then forwards again.
+    PingPong,
+}
+
+/// One picture of an animation.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Frame {
+    /// The part of the sprite sheet the frame is drawn from, in pixels: left, top, width and
+    /// height.
+    pub rect: [u32; 4],
+    /// How long the frame is shown for, in milliseconds.
+    pub duration: u64,
+}
+
+/// A sequence of frames cut from a single sprite sheet.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Animation {
+    /// The name of the texture holding the sprite sheet. Themes can swap it like any other
+    /// sprite.
+    pub texture: String,
+    #[serde(default)]
+    pub playback: Playback,
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    /// Parses a set of animations keyed by name, as found in `textures/animations.json`.
+    /// Animations without frames, or whose frames all take no time, are rejected.
+    pub fn from_json(data: impl AsRef<str>) -> serde_json::Result<BTreeMap<String, Self>> {
+        let animations: BTreeMap<String, Animation> = serde_json::from_str(data.as_ref())?;
+        for (name, animation) in animations.iter() {
+            if animation.frames.iter().all(|frame| frame.duration == 0) {
+                return Err(serde_json::Error::custom(format!(
+                    "animation '{}' needs at least one frame that takes some time",
+                    name
+                )));
+            }
+        }
+        Ok(animations)
+    }
+
+    /// Lists the frames in the order they're shown over one cycle. Ping-pong animations show
+    /// their first and last frames once per cycle.
+    fn get_sequence(&self) -> Vec<usize> {
+        let count = self.frames.len();
+        match self.playback {
+            Playback::PingPong if count > 2 => (0..count).chain((1..count - 1).rev()).collect(),
+            _ => (0..count).collect(),
+        }
+    }
+
+    /// Returns how long one cycle of the animation takes.
+    pub fn get_duration(&self) -> Duration {
+        let millis = self
+            .get_sequence()
+            .iter()
+            .map(|index| self.frames[*index].duration)
+            .sum();
+        Duration::from_millis(millis)
+    }
+
+    /// Returns the index of the frame shown `elapsed` after the animation started.
+    pub fn get_frame_index(&self, elapsed: Duration) -> usize {
+        let sequence = self.get_sequence();
+        let total = self.get_duration().as_millis() as u64;
+        let elapsed = elapsed.as_millis() as u64;
+        let mut time = match self.playback {
+            Playback::Once if elapsed >= total => return sequence[sequence.len() - 1],
+            Playback::Once => elapsed,
+            Playback::Loop | Playback::PingPong => elapsed % total,
+        };
+        for index in sequence.iter() {
+            let duration = self.frames[*index].duration;
+            if time < duration {
+                return *index;
+            }
+            time -= duration;
+        }
+        unreachable!("the time within a cycle is shorter than the cycle")
+    }
+
+    /// Returns whether an animation that plays once has shown its last frame for its full
+    /// duration. Animations that repeat never finish.
+    // breaking bricks go when their shrink tween completes rather than asking this
+    #[allow(dead_code)]
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        self.playback == Playback::Once && elapsed >= self.get_duration()
+    }
+
+    /// Returns the part of a `size` sprite sheet that `frame` is drawn from, as fractions of its
+    /// size, for `DrawParams::source`.
+    pub fn get_source(&self, frame: usize, size: (u32, u32)) -> (Vector2<f32>, Vector2<f32>) {
+        let [left, top, width, height] = self.frames[frame].rect;
+        let size = Vector2::new(size.0 as f32, size.1 as f32);
+        let min = Vector2::new(left as f32, top as f32);
+        let max = min + Vector2::new(width as f32, height as f32);
+        (min.component_div(&size), max.component_div(&size))
+    }
+}
+
+/// Plays an animation for an entity. The player only keeps time, on the simulation clock, so
+/// that the simulation stays independent of the assets; the frames are looked up by name when
+/// it's drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationPlayer {
+    name: String,
+    elapsed: Duration,
+}
+
+impl AnimationPlayer {
+    pub fn new(name: impl Into<String>) -> Self {
+        AnimationPlayer {
+            name: name.into(),
+            elapsed: Duration::from_millis(0),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        self.elapsed += delta;
+    }
+
+    pub fn get_frame_index(&self, animation: &Animation) -> usize {
+        animation.get_frame_index(self.elapsed)
+    }
+
+    #[allow(dead_code)]
+    pub fn is_finished(&self, animation: &Animation) -> bool {
+        animation.is_finished(self.elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation(playback: Playback, durations: &[u64]) -> Animation {
+        Animation {
+            texture: "sheet".to_owned(),
+            playback,
+            frames: durations
+                .iter()
+                .enumerate()
+                .map(|(i, duration)| Frame {
+                    rect: [i as u32 * 10, 0, 10, 10],
+                    duration: *duration,
+                })
+                .collect(),
+        }
+    }
+
+    fn frames_at(animation: &Animation, times: &[u64]) -> Vec<usize> {
+        times
+            .iter()
+            .map(|time| animation.get_frame_index(Duration::from_millis(*time)))
+            .collect()
+    }
+
+    #[test]
+    fn loops_back_to_the_first_frame() {
+        let animation = animation(Playback::Loop, &[100, 50, 100]);
+        assert_eq!(animation.get_duration(), Duration::from_millis(250));
+        assert_eq!(
+            frames_at(&animation, &[0, 99, 100, 149, 150, 249, 250, 375]),
+            [0, 0, 1, 1, 2, 2, 0, 1]
+        );
+        assert!(!animation.is_finished(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let animation = animation(Playback::Once, &[100, 100]);
+        assert_eq!(
+            frames_at(&animation, &[0, 150, 199, 200, 5000]),
+            [0, 1, 1, 1, 1]
+        );
+        assert!(!animation.is_finished(Duration::from_millis(199)));
+        assert!(animation.is_finished(Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn ping_pong_plays_the_ends_once_per_cycle() {
+        let animation = animation(Playback::PingPong, &[10, 10, 10, 10]);
+        assert_eq!(animation.get_duration(), Duration::from_millis(60));
+        let times: Vec<_> = (0..8).map(|i| i * 10).collect();
+        assert_eq!(frames_at(&animation, &times), [0, 1, 2, 3, 2, 1, 0, 1]);
+
+        let short = self::animation(Playback::PingPong, &[10, 20]);
+        assert_eq!(frames_at(&short, &[0, 10, 29, 30]), [0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn frames_without_time_are_skipped() {
+        let animation = animation(Playback::Loop, &[0, 10, 0, 10]);
+        assert_eq!(frames_at(&animation, &[0, 9, 10, 20]), [1, 1, 3, 1]);
+    }
+
+    #[test]
+    fn source_is_a_fraction_of_the_sheet() {
+        let animation = animation(Playback::Loop, &[10, 10]);
+        let (min, max) = animation.get_source(1, (40, 20));
+        assert_eq!(min, Vector2::new(0.25, 0.0));
+        assert_eq!(max, Vector2::new(0.5, 0.5));
+    }
+
+    #[test]
+    fn player_keeps_simulation_time() {
+        let animation = animation(Playback::Once, &[30, 30]);
+        let mut player = AnimationPlayer::new("sheet");
+        assert_eq!(player.get_frame_index(&animation), 0);
+        for _ in 0..3 {
+            player.update(Duration::from_millis(10));
+        }
+        assert_eq!(player.get_frame_index(&animation), 1);
+        assert!(!player.is_finished(&animation));
+        for _ in 0..3 {
+            player.update(Duration::from_millis(10));
+        }
+        assert!(player.is_finished(&animation));
+    }
+
+    #[test]
+    fn parses_and_checks_definitions() {
+        let animations = Animation::from_json(
+            r#"{
+                "spin": {
+                    "texture": "sheet",
+                    "playback": "ping-pong",
+                    "frames": [{"rect": [0, 0, 8, 8], "duration": 50}]
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(animations["spin"].playback, Playback::PingPong);
+        assert_eq!(animations["spin"].frames[0].rect, [0, 0, 8, 8]);
+
+        let err =
+            Animation::from_json(r#"{"empty": {"texture": "sheet", "frames": []}}"#).unwrap_err();
+        assert!(err.to_string().contains("'empty'"));
+        assert!(Animation::from_json(r#"{"bad": {"texture": "sheet", "frame": []}}"#).is_err());
+    }
+}
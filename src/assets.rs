@@ -0,0 +1,512 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use glium::Display;
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::animation::Animation;
+use crate::font::{self, FONT};
+use crate::resources::{Filter, ResourceError, Resources, TextureSettings};
+use crate::theme::Theme;
+
+const BACKGROUND_IMAGE: &[u8] = include_bytes!("../textures/background.jpg");
+const PADDLE_IMAGE: &[u8] = include_bytes!("../textures/paddle.png");
+const BLOCK_IMAGE: &[u8] = include_bytes!("../textures/block.png");
+const BLOCK_SOLID_IMAGE: &[u8] = include_bytes!("../textures/block_solid.png");
+const BALL_IMAGE: &[u8] = include_bytes!("../textures/ball.png");
+const PADDLE_SHEET_IMAGE: &[u8] = include_bytes!("../textures/paddle_sheet.png");
+const BLOCK_BREAK_IMAGE: &[u8] = include_bytes!("../textures/block_break.png");
+const ANIMATIONS: &str = include_str!("../textures/animations.json");
+const SPRITE_VERT: &str = include_str!("../shaders/sprite.vs");
+const SPRITE_FRAG: &str = include_str!("../shaders/sprite.fs");
+const NIGHT_THEME: &str = include_str!("../themes/night.json");
+
+/// The textures compiled into the binary.
+const EMBEDDED_TEXTURES: &[(&str, &[u8])] = &[
+    ("background", BACKGROUND_IMAGE),
+    ("paddle", PADDLE_IMAGE),
+    ("ball", BALL_IMAGE),
+    ("block", BLOCK_IMAGE),
+    ("block_solid", BLOCK_SOLID_IMAGE),
+    ("paddle_sheet", PADDLE_SHEET_IMAGE),
+    ("block_break", BLOCK_BREAK_IMAGE),
+];
+
+/// The shaders compiled into the binary, as vertex and fragment source.
+const EMBEDDED_SHADERS: &[(&str, &str, &str)] = &[("sprite", SPRITE_VERT, SPRITE_FRAG)];
+
+/// The themes compiled into the binary, besides the default theme.
+const EMBEDDED_THEMES: &[(&str, &str)] = &[("night", NIGHT_THEME)];
+
+/// Maps resource names to the files they're loaded from, so that assets can be changed without
+/// rebuilding the game.
+///
+/// Paths are relative to the manifest's own directory. Anything the manifest doesn't mention, or
+/// that fails to load, falls back to the copy compiled into the binary.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub textures: BTreeMap<String, TextureAsset>,
+    #[serde(default)]
+    pub shaders: BTreeMap<String, ShaderAsset>,
+    #[serde(default)]
+    pub themes: BTreeMap<String, PathBuf>,
+    /// The file of animation definitions, usually next to the sprite sheets they cut up.
+    #[serde(default)]
+    pub animations: Option<PathBuf>,
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextureAsset {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub mipmaps: bool,
+}
+
+impl TextureAsset {
+    pub fn get_settings(&self) -> TextureSettings {
+        TextureSettings {
+            filter: self.filter,
+            mipmaps: self.mipmaps,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShaderAsset {
+    pub vertex: PathBuf,
+    pub fragment: PathBuf,
+}
+
+impl Manifest {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)?;
+        let mut manifest: Manifest = serde_json::from_str(&data).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })?;
+        manifest.root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(manifest)
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// Returns every file that the manifest loads from.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let textures = self
+            .textures
+            .values()
+            .map(|asset| self.resolve(&asset.path));
+        let shaders = self
+            .shaders
+            .values()
+            .flat_map(|asset| vec![self.resolve(&asset.vertex), self.resolve(&asset.fragment)]);
+        let themes = self.themes.values().map(|path| self.resolve(path));
+        let animations = self.animations.iter().map(|path| self.resolve(path));
+        textures
+            .chain(shaders)
+            .chain(themes)
+            .chain(animations)
+            .collect()
+    }
+
+    fn load_texture(&self, resources: &mut Resources, name: &str) -> Result<(), ResourceError> {
+        let asset = &self.textures[name];
+        let path = self.resolve(&asset.path);
+        let buffer = fs::read(&path).map_err(|error| ResourceError::Io { path, error })?;
+        resources.load_image_from_memory(name, &buffer, asset.get_settings())
+    }
+
+    fn load_shader(
+        &self,
+        resources: &mut Resources,
+        display: &Display,
+        name: &str,
+    ) -> Result<(), ResourceError> {
+        let asset = &self.shaders[name];
+        let read = |path: &Path| {
+            let path = self.resolve(path);
+            fs::read_to_string(&path).map_err(|error| ResourceError::Io { path, error })
+        };
+        let (vertex, fragment) = (read(&asset.vertex)?, read(&asset.fragment)?);
+        resources.load_shader(display, name, &vertex, &fragment)
+    }
+
+    fn load_theme(&self, resources: &mut Resources, name: &str) -> Result<(), ResourceError> {
+        let path = self.resolve(&self.themes[name]);
+        let data = fs::read_to_string(&path).map_err(|error| ResourceError::Io { path, error })?;
+        load_theme(resources, name, &data)
+    }
+
+    fn load_animations(&self, resources: &mut Resources) -> Result<(), ResourceError> {
+        let path = match &self.animations {
+            Some(path) => self.resolve(path),
+            None => return Ok(()),
+        };
+        let data = fs::read_to_string(&path).map_err(|error| ResourceError::Io { path, error })?;
+        load_animations(resources, &data)
+    }
+}
+
+fn load_animations(resources: &mut Resources, data: &str) -> Result<(), ResourceError> {
+    let animations = Animation::from_json(data).map_err(ResourceError::Animations)?;
+    for (name, animation) in animations {
+        load_animation(resources, name, animation);
+    }
+    Ok(())
+}
+
+fn load_animation(resources: &mut Resources, name: String, animation: Animation) {
+    for texture in get_missing_textures(resources, Some(animation.texture.as_str())) {
+        eprintln!(
+            "animation '{}' uses a texture named '{}' that isn't loaded",
+            name, texture
+        );
+    }
+    resources.load_animation(name, animation);
+}
+
+fn load_theme(resources: &mut Resources, name: &str, data: &str) -> Result<(), ResourceError> {
+    let theme = Theme::from_json(data).map_err(|error| ResourceError::Theme {
+        name: name.to_owned(),
+        error,
+    })?;
+    for texture in get_missing_textures(resources, theme.textures.values().map(String::as_str)) {
+        eprintln!(
+            "theme '{}' uses a texture named '{}' that isn't loaded",
+            name, texture
+        );
+    }
+    resources.load_theme(name, theme);
+    Ok(())
+}
+
+/// Returns the names in `textures` that no texture was loaded under. Themes and animations are
+/// checked as they're loaded, because otherwise a typo only shows up as a placeholder once
+/// something is drawn with it.
+fn get_missing_textures<'a>(
+    resources: &Resources,
+    textures: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    textures
+        .into_iter()
+        .filter(|texture| resources.get_image(texture).is_none())
+        .collect()
+}
+
+/// Loads the textures, themes and animations listed in `manifest`, falling back to the ones
+/// compiled into the binary for anything it doesn't list or that fails to load, and packs the
+/// textures into the atlas. Nothing here needs a GPU, so the result can be drawn by the software
+/// renderer as it is.
+///
+/// Textures that fail to load are logged and drawn as placeholders.
+pub fn load_resources(manifest: Option<&Manifest>) -> Resources {
+    let mut resources = Resources::default();
+
+    if let Some(manifest) = manifest {
+        for name in manifest.textures.keys() {
+            log(manifest.load_texture(&mut resources, name));
+        }
+    }
+    for (name, buffer) in EMBEDDED_TEXTURES.iter() {
+        if resources.get_image(name).is_none() {
+            log(resources.load_image_from_memory(name, buffer, TextureSettings::default()));
+        }
+    }
+    resources.load_image(
+        "blank",
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([255; 4]))),
+        TextureSettings::default(),
+    );
+    // the font is pixel art, so it stays crisp at any size
+    resources.load_image(
+        FONT,
+        DynamicImage::ImageRgba8(font::font_image()),
+        TextureSettings {
+            filter: Filter::Nearest,
+            mipmaps: false,
+        },
+    );
+    resources.pack();
+
+    if let Some(manifest) = manifest {
+        for name in manifest.themes.keys() {
+            log(manifest.load_theme(&mut resources, name));
+        }
+    }
+    for (name, data) in EMBEDDED_THEMES.iter() {
+        if !resources.get_theme_names().contains(name) {
+            log(load_theme(&mut resources, name, data));
+        }
+    }
+
+    if let Some(manifest) = manifest {
+        log(manifest.load_animations(&mut resources));
+    }
+    match Animation::from_json(ANIMATIONS) {
+        Ok(animations) => {
+            for (name, animation) in animations {
+                if resources.get_animation(&name).is_none() {
+                    load_animation(&mut resources, name, animation);
+                }
+            }
+        }
+        Err(error) => log(Err(ResourceError::Animations(error))),
+    }
+    resources
+}
+
+/// Uploads the atlas to the GPU and builds the shaders listed in `manifest`, falling back to the
+/// ones compiled into the binary like `load_resources`.
+///
+/// The game can't be drawn on the GPU at all without its atlas or shader, so those errors are
+/// returned.
+pub fn upload_resources(
+    resources: &mut Resources,
+    display: &Display,
+    manifest: Option<&Manifest>,
+) -> Result<(), ResourceError> {
+    resources.upload(display)?;
+
+    if let Some(manifest) = manifest {
+        for name in manifest.shaders.keys() {
+            log(manifest.load_shader(resources, display, name));
+        }
+    }
+    for (name, vertex, fragment) in EMBEDDED_SHADERS.iter() {
+        if resources.get_shader(name).is_err() {
+            resources.load_shader(display, name, vertex, fragment)?;
+        }
+    }
+    Ok(())
+}
+
+fn log(result: Result<(), ResourceError>) {
+    if let Err(err) = result {
+        eprintln!("{}", err);
+    }
+}
+
+/// Reloads the textures, shaders, themes and animations in `manifest` that were loaded from any of
+/// `changed`.
+///
+/// Assets that fail to reload are logged and keep their previous version, so a half-saved file
+/// doesn't take anything away.
+pub fn reload_resources(
+    resources: &mut Resources,
+    display: &Display,
+    manifest: &Manifest,
+    changed: &[PathBuf],
+) -> Result<(), ResourceError> {
+    let is_changed = |path: &Path| changed.contains(&manifest.resolve(path));
+
+    let mut repack = false;
+    for (name, asset) in manifest.textures.iter() {
+        if is_changed(&asset.path) {
+            match manifest.load_texture(resources, name) {
+                Ok(()) => {
+                    eprintln!("reloaded texture '{}'", name);
+                    repack = true;
+                }
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+    }
+    if repack {
+        resources.pack();
+        resources.upload(display)?;
+    }
+
+    for (name, asset) in manifest.shaders.iter() {
+        if is_changed(&asset.vertex) || is_changed(&asset.fragment) {
+            match manifest.load_shader(resources, display, name) {
+                Ok(()) => eprintln!("reloaded shader '{}'", name),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+    }
+
+    for (name, path) in manifest.themes.iter() {
+        if is_changed(path) {
+            match manifest.load_theme(resources, name) {
+                Ok(()) => eprintln!("reloaded theme '{}'", name),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+    }
+
+    if let Some(path) = &manifest.animations {
+        if is_changed(path) {
+            match manifest.load_animations(resources) {
+                Ok(()) => eprintln!("reloaded animations"),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Notices when files change by polling their modification times and sizes.
+pub struct Watcher {
+    files: HashMap<PathBuf, Option<(SystemTime, u64)>>,
+}
+
+impl Watcher {
+    pub fn new(files: impl IntoIterator<Item = PathBuf>) -> Self {
+        let files = files
+            .into_iter()
+            .map(|path| {
+                let stamp = get_stamp(&path);
+                (path, stamp)
+            })
+            .collect();
+        Watcher { files }
+    }
+
+    /// Returns the files that have been changed, created or deleted since the last poll.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, stamp) in self.files.iter_mut() {
+            let current = get_stamp(path);
+            if current != *stamp {
+                *stamp = current;
+                changed.push(path.clone());
+            }
+        }
+        changed.sort();
+        changed
+    }
+}
+
+fn get_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("breakout-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn manifest_paths_are_relative_to_the_manifest() {
+        let dir = temp_dir("manifest");
+        let path = dir.join("assets.json");
+        fs::write(
+            &path,
+            r#"{
+                "textures": {"paddle": {"path": "textures/paddle.png", "filter": "nearest"}},
+                "shaders": {"sprite": {"vertex": "sprite.vs", "fragment": "sprite.fs"}},
+                "themes": {"night": "themes/night.json"},
+                "animations": "textures/animations.json"
+            }"#,
+        )
+        .unwrap();
+
+        let manifest = Manifest::load(&path).unwrap();
+        let settings = manifest.textures["paddle"].get_settings();
+        assert_eq!(settings.filter, Filter::Nearest);
+        assert!(!settings.mipmaps);
+        assert_eq!(
+            manifest.files(),
+            [
+                dir.join("textures/paddle.png"),
+                dir.join("sprite.vs"),
+                dir.join("sprite.fs"),
+                dir.join("themes/night.json"),
+                dir.join("textures/animations.json")
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn manifest_rejects_unknown_fields() {
+        let dir = temp_dir("unknown");
+        let path = dir.join("assets.json");
+        fs::write(&path, r#"{"texture": {}}"#).unwrap();
+        let err = Manifest::load(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shipped_manifest_matches_embedded_assets() {
+        let manifest = Manifest::load(concat!(env!("CARGO_MANIFEST_DIR"), "/assets.json")).unwrap();
+        for (name, _) in EMBEDDED_TEXTURES.iter() {
+            assert!(manifest.textures.contains_key(*name));
+        }
+        for (name, _, _) in EMBEDDED_SHADERS.iter() {
+            assert!(manifest.shaders.contains_key(*name));
+        }
+        for (name, _) in EMBEDDED_THEMES.iter() {
+            assert!(manifest.themes.contains_key(*name));
+        }
+        assert!(manifest.animations.is_some());
+        for file in manifest.files() {
+            assert!(file.exists(), "{} does not exist", file.display());
+        }
+    }
+
+    #[test]
+    fn missing_textures_are_found_on_load() {
+        let resources = load_resources(None);
+        for name in resources.get_theme_names() {
+            let theme = resources.get_theme(name).unwrap();
+            let textures = theme.textures.values().map(String::as_str);
+            assert!(get_missing_textures(&resources, textures).is_empty());
+        }
+        let animations = Animation::from_json(ANIMATIONS).unwrap();
+        let textures = animations
+            .values()
+            .map(|animation| animation.texture.as_str());
+        assert!(get_missing_textures(&resources, textures).is_empty());
+
+        let textures = ["ball", "night_ball", "paddle"];
+        assert_eq!(
+            get_missing_textures(&resources, textures.iter().cloned()),
+            ["night_ball"]
+        );
+    }
+
+    #[test]
+    fn watcher_reports_changed_files() {
+        let dir = temp_dir("watcher");
+        let (a, b, c) = (dir.join("a"), dir.join("b"), dir.join("c"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+
+        let mut watcher = Watcher::new(vec![a.clone(), b.clone(), c.clone()]);
+        assert!(watcher.poll().is_empty());
+
+        fs::write(&a, "changed").unwrap();
+        fs::write(&c, "created").unwrap();
+        assert_eq!(watcher.poll(), [a.clone(), c.clone()]);
+        assert!(watcher.poll().is_empty());
+
+        fs::remove_file(&b).unwrap();
+        assert_eq!(watcher.poll(), [b]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
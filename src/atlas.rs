@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use image::RgbaImage;
+
+/// Empty pixels left around each image. The image's edge pixels are copied into this border so
+/// that filtering at the edge of a sprite never picks up its neighbours.
+const PADDING: u32 = 1;
+
+/// Where an image ended up in an atlas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Region {
+    /// The index of the atlas page that the image is on.
+    pub page: usize,
+    /// The texture coordinates of the image's top-left corner.
+    pub min: [f32; 2],
+    /// The texture coordinates of the image's bottom-right corner.
+    pub max: [f32; 2],
+}
+
+/// A set of images packed into as few large pages as possible, so that sprites can be drawn
+/// together without switching textures.
+#[derive(Default)]
+pub struct Atlas {
+    pages: Vec<RgbaImage>,
+    regions: HashMap<String, Region>,
+}
+
+impl Atlas {
+    /// Packs `images` onto pages of at most `page_size` square pixels. Images too large for a page
+    /// get a page of their own. Pages are trimmed to the part that was used.
+    ///
+    /// Images are placed in rows, tallest first, with ties broken by name so that the same images
+    /// always produce the same atlas.
+    pub fn pack(images: &BTreeMap<String, RgbaImage>, page_size: u32) -> Self {
+        let mut order: Vec<_> = images.iter().collect();
+        order.sort_by_key(|(name, image)| (std::cmp::Reverse(image.height()), *name));
+
+        // where each image's padding starts, and how much of each page has been used
+        let mut placements = Vec::new();
+        let mut used: Vec<(u32, u32)> = Vec::new();
+        // the page currently being filled, and its cursor and row height
+        let mut current: Option<usize> = None;
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+
+        for (name, image) in order {
+            let (width, height) = (image.width() + PADDING * 2, image.height() + PADDING * 2);
+            if width > page_size || height > page_size {
+                used.push((width, height));
+                placements.push((name, image, used.len() - 1, 0, 0));
+                continue;
+            }
+
+            if current.is_some() && x + width > page_size {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+            let page = match current {
+                Some(page) if y + height <= page_size => page,
+                _ => {
+                    used.push((0, 0));
+                    current = Some(used.len() - 1);
+                    x = 0;
+                    y = 0;
+                    row_height = 0;
+                    used.len() - 1
+                }
+            };
+            placements.push((name, image, page, x, y));
+            used[page].0 = used[page].0.max(x + width);
+            used[page].1 = used[page].1.max(y + height);
+            x += width;
+            row_height = row_height.max(height);
+        }
+
+        let mut atlas = Atlas {
+            pages: used
+                .into_iter()
+                .map(|(width, height)| RgbaImage::new(width, height))
+                .collect(),
+            regions: HashMap::new(),
+        };
+        for (name, image, page, x, y) in placements {
+            atlas.place(name, image, page, x, y);
+        }
+        atlas
+    }
+
+    /// Copies `image` into the page with its padding's top-left corner at `x`, `y`.
+    fn place(&mut self, name: &str, image: &RgbaImage, page: usize, x: u32, y: u32) {
+        let target = &mut self.pages[page];
+        let (width, height) = image.dimensions();
+        for ty in 0..height + PADDING * 2 {
+            for tx in 0..width + PADDING * 2 {
+                let sx = tx.saturating_sub(PADDING).min(width - 1);
+                let sy = ty.saturating_sub(PADDING).min(height - 1);
+                target.put_pixel(x + tx, y + ty, *image.get_pixel(sx, sy));
+            }
+        }
+
+        let (page_width, page_height) = (target.width() as f32, target.height() as f32);
+        let (left, top) = (x + PADDING, y + PADDING);
+        let region = Region {
+            page,
+            min: [left as f32 / page_width, top as f32 / page_height],
+            max: [
+                (left + width) as f32 / page_width,
+                (top + height) as f32 / page_height,
+            ],
+        };
+        self.regions.insert(name.to_owned(), region);
+    }
+
+    /// Adds the pages and regions of `other` after this atlas's own pages.
+    pub fn append(&mut self, other: Atlas) {
+        let offset = self.pages.len();
+        self.pages.extend(other.pages);
+        for (name, mut region) in other.regions {
+            region.page += offset;
+            self.regions.insert(name, region);
+        }
+    }
+
+    pub fn get_region(&self, name: impl AsRef<str>) -> Option<Region> {
+        self.regions.get(name.as_ref()).cloned()
+    }
+
+    /// Returns the pages' pixels, with the top row first.
+    pub fn pages(&self) -> &[RgbaImage] {
+        &self.pages
+    }
+
+    /// Writes each page to `atlas-N.png` in `dir`, for checking how the atlas was packed.
+    pub fn save(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        for (i, page) in self.pages.iter().enumerate() {
+            page.save(dir.join(format!("atlas-{}.png", i)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba};
+
+    /// Returns the pixels that `name` was packed from, along with their dimensions.
+    fn get_pixels(atlas: &Atlas, name: &str) -> ((u32, u32), Vec<u8>) {
+        let region = atlas.get_region(name).unwrap();
+        let page = &atlas.pages()[region.page];
+        let (width, height) = (page.width() as f32, page.height() as f32);
+        let (x, y) = (region.min[0] * width, region.min[1] * height);
+        let (w, h) = (region.max[0] * width - x, region.max[1] * height - y);
+        let pixels = page.view(x as u32, y as u32, w as u32, h as u32).to_image();
+        (pixels.dimensions(), pixels.into_raw())
+    }
+
+    fn get_image_pixels(image: &RgbaImage) -> ((u32, u32), Vec<u8>) {
+        (image.dimensions(), image.clone().into_raw())
+    }
+
+    fn images(sizes: &[(&str, u32, u32)]) -> BTreeMap<String, RgbaImage> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(i, (name, width, height))| {
+                let image = RgbaImage::from_fn(*width, *height, |x, y| {
+                    Rgba([i as u8 + 1, x as u8, y as u8, 255])
+                });
+                (name.to_string(), image)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packs_images_without_overlapping() {
+        let images = images(&[("a", 30, 20), ("b", 20, 30), ("c", 40, 10), ("d", 10, 10)]);
+        let atlas = Atlas::pack(&images, 64);
+
+        for (name, image) in images.iter() {
+            assert_eq!(
+                get_pixels(&atlas, name),
+                get_image_pixels(image),
+                "{} was overwritten",
+                name
+            );
+        }
+        assert!(atlas.get_region("missing").is_none());
+    }
+
+    #[test]
+    fn starts_new_pages_when_full() {
+        let images = images(&[("a", 40, 40), ("b", 40, 40), ("c", 40, 40)]);
+        let atlas = Atlas::pack(&images, 64);
+        assert_eq!(atlas.pages().len(), 3);
+        let pages: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|name| atlas.get_region(name).unwrap().page)
+            .collect();
+        assert_eq!(pages, [0, 1, 2]);
+    }
+
+    #[test]
+    fn fills_rows_tallest_first() {
+        let images = images(&[("short", 10, 8), ("tall", 20, 30), ("wide", 50, 20)]);
+        let atlas = Atlas::pack(&images, 64);
+        assert_eq!(atlas.pages().len(), 1);
+
+        let tall = atlas.get_region("tall").unwrap();
+        let wide = atlas.get_region("wide").unwrap();
+        let short = atlas.get_region("short").unwrap();
+        assert_eq!(atlas.pages()[0].dimensions(), (64, 54));
+        assert_eq!(tall.min, [1.0 / 64.0, 1.0 / 54.0]);
+        assert_eq!(wide.min, [1.0 / 64.0, 33.0 / 54.0]);
+        assert_eq!(short.min, [53.0 / 64.0, 33.0 / 54.0]);
+    }
+
+    #[test]
+    fn oversized_images_get_their_own_page() {
+        let images = images(&[("big", 100, 20), ("small", 10, 10)]);
+        let atlas = Atlas::pack(&images, 64);
+        assert_eq!(atlas.pages().len(), 2);
+
+        let big = atlas.get_region("big").unwrap();
+        assert_eq!(atlas.pages()[big.page].dimensions(), (102, 22));
+        assert_eq!(big.min, [1.0 / 102.0, 1.0 / 22.0]);
+        assert_eq!(big.max, [101.0 / 102.0, 21.0 / 22.0]);
+        assert_eq!(get_pixels(&atlas, "big"), get_image_pixels(&images["big"]));
+    }
+
+    #[test]
+    fn appended_pages_come_after_existing_ones() {
+        let mut atlas = Atlas::pack(&images(&[("a", 40, 40), ("b", 40, 40)]), 64);
+        atlas.append(Atlas::pack(&images(&[("c", 10, 10)]), 64));
+        assert_eq!(atlas.pages().len(), 3);
+        assert_eq!(atlas.get_region("b").unwrap().page, 1);
+        assert_eq!(atlas.get_region("c").unwrap().page, 2);
+    }
+
+    #[test]
+    fn extrudes_edges_into_padding() {
+        let images = images(&[("a", 4, 3)]);
+        let atlas = Atlas::pack(&images, 16);
+        let page = &atlas.pages()[0];
+        let image = &images["a"];
+        assert_eq!(page.get_pixel(0, 0), image.get_pixel(0, 0));
+        assert_eq!(page.get_pixel(5, 0), image.get_pixel(3, 0));
+        assert_eq!(page.get_pixel(0, 4), image.get_pixel(0, 2));
+        assert_eq!(page.get_pixel(5, 4), image.get_pixel(3, 2));
+        assert_eq!(page.get_pixel(3, 0), image.get_pixel(2, 0));
+        assert_eq!(page.dimensions(), (6, 5));
+    }
+}
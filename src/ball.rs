@@ -1,75 +1,698 @@
 use std::time::Duration;
 
 use nalgebra::Vector2;
+use rand::Rng;
 
+use crate::collision::{Collider, Hit, Shape};
+use crate::component::{Sprite, Transform};
+use crate::ecs::{EntityId, World};
 use crate::entity::Entity;
-use crate::level::Brick;
-use crate::{GAME_HEIGHT, GAME_WIDTH};
+use crate::level::{BallSpeed, BrickId, Level};
+use crate::math::{reflect, Circle};
+use crate::player::Player;
+use crate::renderer::{DrawParams, Layer, Renderer};
+use crate::GAME_WIDTH;
+
+/// The most surfaces the ball can bounce off in a single update.
+const MAX_BOUNCES: usize = 8;
+
+/// The furthest from vertical that the ball can be launched, in radians.
+const MAX_LAUNCH_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
+
+/// How much of the paddle's horizontal velocity is passed on to the ball when it bounces off.
+const SPIN_TRANSFER: f32 = 0.4;
+
+/// How quickly the player can turn their aim while the ball is stuck, in radians per second.
+const AIM_SPEED: f32 = 1.5;
+
+/// The size of the ball, in pixels.
+const RADIUS: f32 = 12.5;
+
+/// How close together along the ball's path, in pixels, hits count as happening at once.
+const SAME_HIT: f32 = 1e-3;
+
+enum Obstacle {
+    Wall,
+    Paddle,
+    Brick(BrickId),
+}
 
 pub struct Ball {
-    position: Vector2<f32>,
-    velocity: Vector2<f32>,
-    radius: f32,
+    id: EntityId,
+    speed: f32,
+    settings: BallSpeed,
+    paddle_hits: u32,
     stuck: bool,
+    /// The launch angle the player has chosen, measured clockwise from straight up.
+    aim: Option<f32>,
 }
 
 impl Ball {
-    pub fn new(player: impl Into<Vector2<f32>>) -> Self {
-        let player = player.into();
-        let radius = 12.5;
-        Ball {
-            velocity: [100.0, -350.0].into(),
-            position: [(GAME_WIDTH as f32 - radius) / 2.0, player[1] - radius * 2.0].into(),
-            radius,
+    /// Spawns a ball stuck to the middle of the top of the paddle.
+    ///
+    /// The ball is swept, so the world's movement system leaves it alone and `update_position`
+    /// moves it instead.
+    pub fn new(world: &mut World, player: &Player, settings: BallSpeed) -> Self {
+        let (position, size) = (player.get_position(world), player.get_size(world));
+        let id = world.spawn();
+        // the box around the ball, which it fills
+        let transform = Transform::new(
+            [
+                position[0] + size[0] / 2.0 - RADIUS,
+                position[1] - RADIUS * 2.0,
+            ],
+            Vector2::repeat(RADIUS * 2.0),
+        );
+        world.transforms.insert(id, transform);
+        world.sprites.insert(id, Sprite::new("ball"));
+        world.colliders.insert(id, Collider::Circle);
+        world.velocities.insert(id, get_launch_direction(0.0));
+        world.swept.insert(id, ());
+        let mut ball = Ball {
+            id,
+            speed: 0.0,
+            settings,
+            paddle_hits: 0,
             stuck: true,
+            aim: None,
+        };
+        ball.set_speed(world, settings.base);
+        ball
+    }
+
+    pub fn get_circle(&self, world: &World) -> Circle {
+        let transform = self.get_transform(world);
+        Circle {
+            center: transform.get_center(),
+            radius: transform.size[0] / 2.0,
         }
     }
 
-    pub fn get_radius(&self) -> f32 {
-        self.radius
+    pub fn get_velocity(&self, world: &World) -> Vector2<f32> {
+        *world.velocities.get(self.id).unwrap()
     }
 
-    pub fn unstick(&mut self) {
-        self.stuck = false;
+    /// Returns how many times the ball has bounced off the paddle since it was launched.
+    pub fn get_paddle_hits(&self) -> u32 {
+        self.paddle_hits
+    }
+
+    pub fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    fn set_speed(&mut self, world: &mut World, speed: f32) {
+        self.speed = speed;
+        self.apply_speed(world, None);
+    }
+
+    /// Keeps the speed between the level's limits and scales the velocity to it, steepening it if
+    /// it's too close to horizontal.
+    ///
+    /// `normal` faces away from the surface the ball has just bounced off, if it has. A bounce that
+    /// leaves the ball with too little vertical speed sends it away from that surface, since a
+    /// soft bounce off the ceiling leaves it with none and it would otherwise be steepened
+    /// straight back into it.
+    fn apply_speed(&mut self, world: &mut World, normal: Option<Vector2<f32>>) {
+        self.speed = self.speed.max(self.settings.min).min(self.settings.max);
+        let speed = self.speed;
+        let velocity = world.velocities.get_mut(self.id).unwrap();
+        let direction = match normal {
+            _ if velocity.norm() > 0.0 => velocity.normalize(),
+            Some(normal) => normal.normalize(),
+            None => Vector2::new(0.0, -1.0),
+        };
+        *velocity = direction * speed;
+
+        let min_vertical = self.settings.min_vertical * speed;
+        if velocity[1].abs() < min_vertical {
+            let away = match normal {
+                Some(normal) if normal[1] != 0.0 => normal[1],
+                _ => velocity[1],
+            };
+            let vertical = if away > 0.0 {
+                min_vertical
+            } else {
+                -min_vertical
+            };
+            let horizontal = (speed * speed - min_vertical * min_vertical).sqrt();
+            *velocity = [horizontal.copysign(velocity[0]), vertical].into();
+        }
+    }
+
+    pub fn is_stuck(&self) -> bool {
+        self.stuck
+    }
+
+    pub fn aim_left(&mut self, delta: Duration) {
+        self.aim_by(-AIM_SPEED * delta.as_millis() as f32 / 1000.0);
+    }
+
+    pub fn aim_right(&mut self, delta: Duration) {
+        self.aim_by(AIM_SPEED * delta.as_millis() as f32 / 1000.0);
     }
 
-    pub fn move_by(&mut self, by: f32) {
+    fn aim_by(&mut self, by: f32) {
         if self.stuck {
-            self.position[0] += by;
+            let aim = self.aim.unwrap_or(0.0) + by;
+            self.aim = Some(aim.clamp(-MAX_LAUNCH_ANGLE, MAX_LAUNCH_ANGLE));
         }
     }
 
-    pub fn update_position(&mut self, delta: Duration) {
-        let delta = delta.as_millis() as f32 / 1000.0;
+    /// Launches the ball in the direction the player aimed it, or at a random angle from `rng` if
+    /// they didn't.
+    ///
+    /// `rng` is only used when the ball is actually launched, so calling this every tick while
+    /// the launch button is held doesn't disturb the random sequence.
+    pub fn unstick(&mut self, world: &mut World, rng: &mut impl Rng) {
         if !self.stuck {
-            self.position += self.velocity * delta;
-
-            if self.position[0] <= 0.0 {
-                self.velocity[0] = -self.velocity[0];
-                self.position[0] = 0.0;
-            } else if self.position[0] + self.radius >= GAME_WIDTH as f32 {
-                self.velocity[0] = -self.velocity[0];
-                self.position[0] = GAME_WIDTH as f32 - self.radius;
+            return;
+        }
+        let angle = match self.aim.take() {
+            Some(angle) => angle,
+            None => rng.gen_range(-MAX_LAUNCH_ANGLE, MAX_LAUNCH_ANGLE),
+        };
+        world
+            .velocities
+            .insert(self.id, get_launch_direction(angle));
+        self.set_speed(world, self.speed);
+        self.stuck = false;
+    }
+
+    pub fn move_by(&self, world: &mut World, by: f32) {
+        if self.stuck {
+            world.transforms.get_mut(self.id).unwrap().position[0] += by;
+        }
+    }
+
+    /// Moves the ball for `delta`, bouncing off the walls, the paddle and any bricks in its path
+    /// and destroying the bricks that it hits.
+    ///
+    /// Each bounce is found by sweeping the ball along its path for the rest of the update with
+    /// the world's collision system and taking the earliest hit, so fast balls can't pass through
+    /// anything. The ball reflects off
+    /// the normal of the surface where it touches, so it glances off corners. Everything it
+    /// touches at the same moment is bounced off together, so meeting two bricks at once doesn't
+    /// reflect it once for each of them.
+    pub fn update_position(&mut self, delta: Duration, level: &mut Level, player: &Player) {
+        if self.stuck {
+            return;
+        }
+
+        let mut remaining = delta.as_millis() as f32 / 1000.0;
+        let speed = self.speed + self.settings.ramp_per_second * remaining;
+        self.set_speed(level.get_world_mut(), speed);
+
+        for _ in 0..MAX_BOUNCES {
+            let world = level.get_world();
+            let circle = self.get_circle(world);
+            let (center, radius) = (circle.center, circle.radius);
+            let shape = Shape::Circle(circle);
+            let motion = self.get_velocity(world) * remaining;
+
+            let mut hits: Vec<(Hit, Obstacle)> = Vec::new();
+            let mut wall = |time: Option<f32>, normal: [f32; 2]| {
+                if let Some(time) = time {
+                    let normal = normal.into();
+                    hits.push((Hit { time, normal }, Obstacle::Wall));
+                }
+            };
+            if motion[0] < 0.0 {
+                wall(sweep_wall(center[0], motion[0], radius), [1.0, 0.0]);
+            } else if motion[0] > 0.0 {
+                let limit = GAME_WIDTH as f32 - radius;
+                wall(sweep_wall(center[0], motion[0], limit), [-1.0, 0.0]);
+            }
+            if motion[1] < 0.0 {
+                wall(sweep_wall(center[1], motion[1], radius), [0.0, 1.0]);
+            }
+
+            if let Some(hit) = world.sweep(&shape, motion, player.get_id()) {
+                hits.push((hit, Obstacle::Paddle));
+            }
+
+            // destroyed bricks have no collider, so the sweep passes through them
+            let (min, max) = circle.get_swept_bounds(motion);
+            for id in level.query(min, max) {
+                let brick = level.get_brick(id).unwrap();
+                if let Some(hit) = world.sweep(&shape, motion, brick.get_id()) {
+                    hits.push((hit, Obstacle::Brick(id)));
+                }
             }
 
-            if self.position[1] <= 0.0 {
-                self.velocity[1] = -self.velocity[1];
-                self.position[1] = 0.0;
+            let first = hits.iter().map(|(hit, _)| hit.time).min_by(f32::total_cmp);
+            let transform = level.get_world_mut().transforms.get_mut(self.id).unwrap();
+            let first = match first {
+                Some(first) => first,
+                None => {
+                    transform.position += motion;
+                    break;
+                }
+            };
+            transform.position += motion * first;
+            remaining *= 1.0 - first;
+
+            let same_time = SAME_HIT / motion.norm();
+            let mut normal = Vector2::zeros();
+            let mut spin = 0.0;
+            for (hit, obstacle) in hits {
+                if hit.time - first > same_time {
+                    continue;
+                }
+                normal += hit.normal;
+                match obstacle {
+                    Obstacle::Wall => (),
+                    Obstacle::Paddle => {
+                        self.paddle_hits += 1;
+                        if self.settings.hits > 0 && self.paddle_hits % self.settings.hits == 0 {
+                            self.speed += self.settings.ramp_per_hits;
+                        }
+                        spin = player.get_velocity(level.get_world()) * SPIN_TRANSFER;
+                    }
+                    Obstacle::Brick(id) => {
+                        if level.get_brick(id).unwrap().is_destructible() {
+                            level.destroy_brick(id);
+                        }
+                    }
+                }
             }
+            let world = level.get_world_mut();
+            let velocity = world.velocities.get_mut(self.id).unwrap();
+            // surfaces facing each other cancel out, leaving the ball to go back the way it came
+            if normal == Vector2::zeros() {
+                normal = -*velocity;
+            }
+            *velocity = reflect(*velocity, normal, self.settings.restitution);
+            velocity[0] += spin;
+            self.apply_speed(world, Some(normal));
         }
     }
+
+    /// Draws a trail of dots fading out in the direction the ball will be launched, once the
+    /// player has aimed it. The ball itself is drawn with the rest of the world.
+    pub fn render_aim(&self, world: &World, renderer: &mut dyn Renderer) {
+        if let Some(aim) = self.aim {
+            let direction = get_launch_direction(aim);
+            let circle = self.get_circle(world);
+            for i in 1..=5 {
+                let center = circle.center + direction * (circle.radius + 16.0 * i as f32);
+                let params = DrawParams {
+                    tint: [1.0, 1.0, 1.0, 1.0 - i as f32 * 0.15].into(),
+                    ..DrawParams::new(center - Vector2::repeat(2.0), [4.0, 4.0])
+                        .with_layer(Layer::Effects)
+                };
+                renderer.draw_sprite("blank", &params);
+            }
+        }
+    }
+}
+
+/// Returns the unit vector `angle` radians clockwise from straight up.
+fn get_launch_direction(angle: f32) -> Vector2<f32> {
+    [angle.sin(), -angle.cos()].into()
+}
+
+/// Finds when a coordinate moving by `motion` reaches `limit`, if it does within this step.
+fn sweep_wall(position: f32, motion: f32, limit: f32) -> Option<f32> {
+    let time = ((limit - position) / motion).max(0.0);
+    if time <= 1.0 {
+        Some(time)
+    } else {
+        None
+    }
 }
 
 impl Entity for Ball {
-    fn get_sprite(&self) -> &str {
-        "ball"
+    fn get_id(&self) -> EntityId {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GAME_HEIGHT;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    const STEP: Duration = Duration::from_millis(100);
+
+    /// Puts the paddle in its usual place in `level`, and a ball in flight at `center` that
+    /// keeps its speed.
+    fn play(mut level: Level, center: [f32; 2], velocity: [f32; 2]) -> (Level, Player, Ball) {
+        let velocity = Vector2::from(velocity);
+        let settings = BallSpeed {
+            base: velocity.norm(),
+            min: 1.0,
+            max: 1e6,
+            ramp_per_second: 0.0,
+            ramp_per_hits: 0.0,
+            hits: 0,
+            min_vertical: 0.0,
+            restitution: 1.0,
+        };
+        let world = level.get_world_mut();
+        let player = Player::new(world);
+        let mut ball = Ball::new(world, &player, settings);
+        ball.stuck = false;
+        place(&ball, world, center, velocity);
+        (level, player, ball)
+    }
+
+    fn place(ball: &Ball, world: &mut World, center: [f32; 2], velocity: Vector2<f32>) {
+        let transform = world.transforms.get_mut(ball.id).unwrap();
+        transform.position = Vector2::from(center) - Vector2::repeat(RADIUS);
+        world.velocities.insert(ball.id, velocity);
+    }
+
+    /// The top of the paddle, and its centre, where `Player::new` puts it.
+    fn paddle_top() -> (f32, f32) {
+        let mut world = World::default();
+        let player = Player::new(&mut world);
+        let (position, size) = (player.get_position(&world), player.get_size(&world));
+        (position[1], position[0] + size[0] / 2.0)
     }
 
-    fn get_position(&self) -> Vector2<f32> {
-        self.position
+    fn empty_level() -> Level {
+        Level::from_json(r#"{"width": 1, "height": 1, "map": []}"#)
     }
 
-    fn get_size(&self) -> Vector2<f32> {
-        [self.radius * 2.0, self.radius * 2.0].into()
+    fn brick_level(code: u32) -> Level {
+        Level::from_json(format!(
+            r#"{{"width": 1, "height": 1, "map": [],
+                "bricks": [{{"x": 400, "y": 200, "width": 100, "height": 40, "code": {}}}]}}"#,
+            code
+        ))
+    }
+
+    fn assert_ball(ball: &Ball, level: &Level, center: [f32; 2], velocity: [f32; 2]) {
+        let world = level.get_world();
+        let (actual_center, actual_velocity) =
+            (ball.get_circle(world).center, ball.get_velocity(world));
+        for i in 0..2 {
+            assert!(
+                (actual_center[i] - center[i]).abs() < 1e-2
+                    && (actual_velocity[i] - velocity[i]).abs() < 1e-2,
+                "ball is at {:?} moving {:?}, expected {:?} moving {:?}",
+                actual_center,
+                actual_velocity,
+                center,
+                velocity
+            );
+        }
+    }
+
+    #[test]
+    fn spawns_touching_the_paddle() {
+        let mut world = World::default();
+        let player = Player::new(&mut world);
+        player.move_left(&mut world, Duration::from_millis(200));
+        world.update(Duration::from_millis(200));
+        let ball = Ball::new(&mut world, &player, BallSpeed::default());
+        let circle = ball.get_circle(&world);
+        let (position, size) = (player.get_position(&world), player.get_size(&world));
+        assert_eq!(circle.center[0], position[0] + size[0] / 2.0);
+        assert_eq!(circle.center[1] + circle.radius, position[1]);
+        assert_eq!(
+            ball.get_position(&world),
+            circle.center - Vector2::repeat(12.5)
+        );
+        assert_eq!(ball.get_size(&world), Vector2::repeat(25.0));
+    }
+
+    #[test]
+    fn is_left_alone_by_the_movement_system() {
+        let (mut level, _, ball) = play(empty_level(), [500.0, 300.0], [0.0, -1000.0]);
+        level.update(STEP);
+        assert_ball(&ball, &level, [500.0, 300.0], [0.0, -1000.0]);
+    }
+
+    #[test]
+    fn bounces_off_left_wall() {
+        let (mut level, player, mut ball) = play(empty_level(), [20.0, 300.0], [-1000.0, 0.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [105.0, 300.0], [1000.0, 0.0]);
+    }
+
+    #[test]
+    fn bounces_off_right_wall() {
+        let width = GAME_WIDTH as f32;
+        let (mut level, player, mut ball) =
+            play(empty_level(), [width - 20.0, 300.0], [1000.0, 0.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [width - 105.0, 300.0], [-1000.0, 0.0]);
+    }
+
+    #[test]
+    fn bounces_off_top_wall() {
+        let (mut level, player, mut ball) = play(empty_level(), [500.0, 20.0], [0.0, -1000.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [500.0, 105.0], [0.0, 1000.0]);
+    }
+
+    #[test]
+    fn falls_through_bottom() {
+        let height = GAME_HEIGHT as f32;
+        let (mut level, player, mut ball) =
+            play(empty_level(), [100.0, height - 20.0], [0.0, 1000.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [100.0, height + 80.0], [0.0, 1000.0]);
+    }
+
+    #[test]
+    fn bounces_off_brick_top() {
+        let (mut level, player, mut ball) = play(brick_level(2), [450.0, 150.0], [0.0, 1000.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [450.0, 125.0], [0.0, -1000.0]);
+        assert_eq!(level.count_remaining(), 0);
+    }
+
+    #[test]
+    fn bounces_off_brick_bottom() {
+        let (mut level, player, mut ball) = play(brick_level(2), [450.0, 300.0], [0.0, -1000.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [450.0, 305.0], [0.0, 1000.0]);
+        assert_eq!(level.count_remaining(), 0);
+    }
+
+    #[test]
+    fn bounces_off_brick_left() {
+        let (mut level, player, mut ball) = play(brick_level(2), [350.0, 220.0], [1000.0, 0.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [325.0, 220.0], [-1000.0, 0.0]);
+        assert_eq!(level.count_remaining(), 0);
+    }
+
+    #[test]
+    fn bounces_off_brick_right() {
+        let (mut level, player, mut ball) = play(brick_level(2), [550.0, 220.0], [-1000.0, 0.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [575.0, 220.0], [1000.0, 0.0]);
+        assert_eq!(level.count_remaining(), 0);
+    }
+
+    #[test]
+    fn solid_bricks_survive() {
+        let (mut level, player, mut ball) = play(brick_level(1), [450.0, 150.0], [0.0, 1000.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [450.0, 125.0], [0.0, -1000.0]);
+        assert!(level.bricks().all(|(_, brick)| !brick.is_destroyed()));
+    }
+
+    #[test]
+    fn glances_off_brick_corners() {
+        // touches the top-right corner with its centre 3/5 of its radius to the side
+        let (mut level, player, mut ball) = play(brick_level(2), [507.5, 150.0], [0.0, 1000.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [565.1, 173.2], [960.0, -280.0]);
+        assert_eq!(level.count_remaining(), 0);
+    }
+
+    #[test]
+    fn bounces_once_off_bricks_hit_together() {
+        // the ball meets the bottoms of both bricks at once, right under the join
+        let level = Level::from_json(
+            r#"{"width": 1, "height": 1, "map": [], "bricks": [
+                {"x": 400, "y": 200, "width": 100, "height": 40, "code": 2},
+                {"x": 500, "y": 200, "width": 100, "height": 40, "code": 2}]}"#,
+        );
+        let (mut level, player, mut ball) = play(level, [500.0, 300.0], [0.0, -1000.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [500.0, 305.0], [0.0, 1000.0]);
+        assert_eq!(level.count_remaining(), 0);
+    }
+
+    #[test]
+    fn bounces_back_out_of_inside_corners() {
+        // between a brick above and a brick to the right, meeting both at once
+        let level = Level::from_json(
+            r#"{"width": 1, "height": 1, "map": [], "bricks": [
+                {"x": 400, "y": 200, "width": 100, "height": 40, "code": 2},
+                {"x": 500, "y": 240, "width": 100, "height": 40, "code": 2}]}"#,
+        );
+        let (mut level, player, mut ball) = play(level, [457.5, 282.5], [500.0, -500.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [467.5, 272.5], [-500.0, 500.0]);
+        assert_eq!(level.count_remaining(), 0);
+    }
+
+    #[test]
+    fn restitution_flattens_bounces() {
+        let (mut level, player, mut ball) = play(empty_level(), [20.0, 300.0], [-300.0, -400.0]);
+        ball.settings.restitution = 0.5;
+        ball.update_position(STEP, &mut level, &player);
+        // half the speed into the wall is kept, then the ball is brought back up to speed
+        let velocity = Vector2::new(150.0, -400.0).normalize() * 500.0;
+        assert!((ball.get_velocity(level.get_world()) - velocity).norm() < 1e-2);
+    }
+
+    #[test]
+    fn soft_bounces_leave_the_ceiling() {
+        let (mut level, player, mut ball) = play(empty_level(), [500.0, 32.5], [300.0, -400.0]);
+        ball.settings.restitution = 0.0;
+        ball.settings.min_vertical = 0.3;
+        ball.update_position(STEP, &mut level, &player);
+        // nothing is left of the speed into the ceiling, so the ball is steepened back down
+        let horizontal = 500.0 * (1.0f32 - 0.09).sqrt();
+        let center = [515.0 + horizontal * 0.05, RADIUS + 7.5];
+        assert_ball(&ball, &level, center, [horizontal, 150.0]);
+    }
+
+    #[test]
+    fn soft_bounces_straight_up_come_back_down() {
+        let (mut level, player, mut ball) = play(empty_level(), [500.0, 32.5], [0.0, -500.0]);
+        ball.settings.restitution = 0.0;
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [500.0, RADIUS + 30.0], [0.0, 500.0]);
+    }
+
+    #[test]
+    fn does_not_tunnel_through_thin_bricks() {
+        let level = Level::from_json(
+            r#"{"width": 1, "height": 1, "map": [],
+                "bricks": [{"x": 400, "y": 300, "width": 100, "height": 2, "code": 1}]}"#,
+        );
+        let (mut level, player, mut ball) = play(level, [450.0, 100.0], [0.0, 3000.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [450.0, 175.0], [0.0, -3000.0]);
+    }
+
+    #[test]
+    fn bounces_off_paddle() {
+        let (top, center) = paddle_top();
+        let (mut level, player, mut ball) =
+            play(empty_level(), [center, top - 62.5], [0.0, 1000.0]);
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [center, top - 62.5], [0.0, -1000.0]);
+    }
+
+    #[test]
+    fn moving_paddle_puts_spin_on_the_ball() {
+        let (top, center) = paddle_top();
+        let (mut level, player, mut ball) =
+            play(empty_level(), [center, top - 62.5], [0.0, 1000.0]);
+        let world = level.get_world_mut();
+        player.move_right(world, Duration::from_millis(10));
+        assert!(player.get_velocity(world) > 0.0);
+
+        ball.update_position(STEP, &mut level, &player);
+        let velocity = ball.get_velocity(level.get_world());
+        assert!(velocity[0] > 0.0 && velocity[1] < 0.0);
+        assert!((velocity.norm() - 1000.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let settings = BallSpeed {
+            base: 2000.0,
+            ..BallSpeed::default()
+        };
+        let mut world = World::default();
+        let player = Player::new(&mut world);
+        let ball = Ball::new(&mut world, &player, settings);
+        assert_eq!(ball.get_speed(), settings.max);
+        assert!((ball.get_velocity(&world).norm() - settings.max).abs() < 1e-2);
+    }
+
+    #[test]
+    fn speed_ramps_over_time() {
+        let (mut level, player, mut ball) = play(empty_level(), [500.0, 300.0], [0.0, -400.0]);
+        ball.settings.ramp_per_second = 10.0;
+        ball.update_position(Duration::from_secs(1), &mut level, &player);
+        assert!((ball.get_speed() - 410.0).abs() < 1e-2);
+        assert!((ball.get_velocity(level.get_world()).norm() - 410.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn speed_ramps_per_paddle_hits() {
+        let (top, center) = paddle_top();
+        let (mut level, player, mut ball) =
+            play(empty_level(), [center, top - 62.5], [0.0, 1000.0]);
+        ball.settings.hits = 2;
+        ball.settings.ramp_per_hits = 50.0;
+
+        ball.update_position(STEP, &mut level, &player);
+        assert_eq!(ball.get_speed(), 1000.0);
+
+        let velocity = Vector2::new(0.0, 1000.0);
+        place(&ball, level.get_world_mut(), [center, top - 62.5], velocity);
+        ball.update_position(STEP, &mut level, &player);
+        assert_eq!(ball.get_speed(), 1050.0);
+        assert!((ball.get_velocity(level.get_world())[1] + 1050.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn flat_angles_are_steepened() {
+        let (mut level, player, mut ball) = play(empty_level(), [500.0, 300.0], [400.0, 1.0]);
+        ball.settings.min_vertical = 0.5;
+        ball.update_position(STEP, &mut level, &player);
+        let velocity = ball.get_velocity(level.get_world());
+        assert!((velocity.norm() - 400.0).abs() < 1e-2);
+        assert!((velocity[1] - 200.0).abs() < 1e-2);
+        assert!(velocity[0] > 0.0);
+    }
+
+    #[test]
+    fn reflections_preserve_speed_and_angle() {
+        let (mut level, player, mut ball) = play(empty_level(), [20.0, 300.0], [-300.0, -400.0]);
+        ball.settings.min_vertical = 0.3;
+        ball.update_position(STEP, &mut level, &player);
+        assert_ball(&ball, &level, [35.0, 260.0], [300.0, -400.0]);
+        assert!((ball.get_speed() - 500.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn launches_where_aimed() {
+        let mut world = World::default();
+        let player = Player::new(&mut world);
+        let mut ball = Ball::new(&mut world, &player, BallSpeed::default());
+        ball.aim_right(Duration::from_secs(10));
+        assert_eq!(ball.aim, Some(MAX_LAUNCH_ANGLE));
+        ball.aim_left(Duration::from_millis(200));
+
+        ball.unstick(&mut world, &mut Pcg32::seed_from_u64(0));
+        let angle = MAX_LAUNCH_ANGLE - AIM_SPEED * 0.2;
+        let speed = BallSpeed::default().base;
+        let velocity = ball.get_velocity(&world);
+        assert!(!ball.is_stuck());
+        assert!((velocity[0] - angle.sin() * speed).abs() < 1e-2);
+        assert!((velocity[1] + angle.cos() * speed).abs() < 1e-2);
+    }
+
+    #[test]
+    fn random_launches_follow_the_seed() {
+        let launch = |seed| {
+            let mut world = World::default();
+            let player = Player::new(&mut world);
+            let mut ball = Ball::new(&mut world, &player, BallSpeed::default());
+            ball.unstick(&mut world, &mut Pcg32::seed_from_u64(seed));
+            ball.get_velocity(&world)
+        };
+        let velocity = launch(7);
+        assert_eq!(velocity, launch(7));
+        assert_ne!(velocity, launch(8));
+
+        let angle = velocity[0].atan2(-velocity[1]);
+        assert!(angle.abs() <= MAX_LAUNCH_ANGLE);
     }
 }
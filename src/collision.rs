@@ -0,0 +1,873 @@
+use nalgebra::Vector2;
+
+use crate::component::Transform;
+use crate::math::Circle;
+
+/// How close a sweep has to bring two shapes before they count as touching, in pixels.
+const SWEEP_TOLERANCE: f32 = 1e-3;
+
+/// The most steps a sweep takes towards a shape before giving up on reaching it.
+const MAX_SWEEP_STEPS: usize = 64;
+
+/// The shape an entity collides as, fitted to the box of its transform.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Collider {
+    /// The whole box, which stays upright whatever the transform's rotation.
+    Aabb,
+    /// The largest circle that fits in the middle of the box.
+    Circle,
+    /// The whole box, turned with the transform.
+    // nothing in the game collides as a turned box, a capsule or a polygon yet
+    #[allow(dead_code)]
+    OrientedBox,
+    /// The box with its short ends rounded off, turned with the transform.
+    #[allow(dead_code)]
+    Capsule,
+    /// A convex polygon, turned with the transform. Its points are fractions of the box from its
+    /// top-left corner, so `[1.0, 1.0]` is the bottom-right corner.
+    #[allow(dead_code)]
+    Polygon(Vec<Vector2<f32>>),
+}
+
+impl Collider {
+    /// Places the collider on `transform`.
+    pub fn get_shape(&self, transform: &Transform) -> Shape {
+        let (center, size) = (transform.get_center(), transform.size);
+        let turn = |point: Vector2<f32>| center + rotate(point - center, transform.rotation);
+        match self {
+            Collider::Aabb => Shape::Aabb {
+                min: transform.position,
+                max: transform.get_max(),
+            },
+            Collider::Circle => Shape::Circle(Circle {
+                center,
+                radius: size[0].min(size[1]) / 2.0,
+            }),
+            Collider::OrientedBox => Shape::Obb {
+                center,
+                half_size: size / 2.0,
+                rotation: transform.rotation,
+            },
+            Collider::Capsule => {
+                let radius = size[0].min(size[1]) / 2.0;
+                let reach = if size[0] > size[1] {
+                    Vector2::new(size[0] / 2.0 - radius, 0.0)
+                } else {
+                    Vector2::new(0.0, size[1] / 2.0 - radius)
+                };
+                Shape::Capsule {
+                    start: turn(center - reach),
+                    end: turn(center + reach),
+                    radius,
+                }
+            }
+            Collider::Polygon(points) => Shape::Polygon(
+                points
+                    .iter()
+                    .map(|point| turn(transform.position + point.component_mul(&size)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A shape placed in the world.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Aabb {
+        min: Vector2<f32>,
+        max: Vector2<f32>,
+    },
+    Circle(Circle),
+    /// A box reaching `half_size` either side of `center`, turned `rotation` radians clockwise.
+    Obb {
+        center: Vector2<f32>,
+        half_size: Vector2<f32>,
+        rotation: f32,
+    },
+    /// Everything within `radius` of the segment from `start` to `end`.
+    Capsule {
+        start: Vector2<f32>,
+        end: Vector2<f32>,
+        radius: f32,
+    },
+    /// A convex polygon, with its points in either winding order.
+    Polygon(Vec<Vector2<f32>>),
+}
+
+/// How two shapes overlap.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact {
+    /// The direction from the first shape to the second, as a unit vector. Moving the second
+    /// shape `depth` along it separates them.
+    pub normal: Vector2<f32>,
+    pub depth: f32,
+}
+
+/// Where a moving shape first touches another.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// How far through its motion the shape got, from 0 to 1.
+    pub time: f32,
+    /// The normal of the surface that was hit, as a unit vector pointing back out towards the
+    /// moving shape.
+    pub normal: Vector2<f32>,
+}
+
+/// A shape as a convex core of one point, two or more, grown by a radius. Every shape can be
+/// tested against every other this way.
+struct Core {
+    points: Vec<Vector2<f32>>,
+    radius: f32,
+}
+
+impl Shape {
+    fn get_core(&self) -> Core {
+        let (points, radius) = match self {
+            Shape::Aabb { min, max } => (
+                vec![*min, [max[0], min[1]].into(), *max, [min[0], max[1]].into()],
+                0.0,
+            ),
+            Shape::Circle(circle) => (vec![circle.center], circle.radius),
+            Shape::Obb {
+                center,
+                half_size,
+                rotation,
+            } => {
+                let corner = |x: f32, y: f32| {
+                    center + rotate(Vector2::new(x * half_size[0], y * half_size[1]), *rotation)
+                };
+                (
+                    vec![
+                        corner(-1.0, -1.0),
+                        corner(1.0, -1.0),
+                        corner(1.0, 1.0),
+                        corner(-1.0, 1.0),
+                    ],
+                    0.0,
+                )
+            }
+            Shape::Capsule { start, end, radius } => (vec![*start, *end], *radius),
+            Shape::Polygon(points) => (points.clone(), 0.0),
+        };
+        Core { points, radius }
+    }
+
+    /// Returns the shape moved by `by`.
+    pub fn translate(&self, by: Vector2<f32>) -> Shape {
+        match self {
+            Shape::Aabb { min, max } => Shape::Aabb {
+                min: min + by,
+                max: max + by,
+            },
+            Shape::Circle(circle) => Shape::Circle(Circle {
+                center: circle.center + by,
+                radius: circle.radius,
+            }),
+            Shape::Obb {
+                center,
+                half_size,
+                rotation,
+            } => Shape::Obb {
+                center: center + by,
+                half_size: *half_size,
+                rotation: *rotation,
+            },
+            Shape::Capsule { start, end, radius } => Shape::Capsule {
+                start: start + by,
+                end: end + by,
+                radius: *radius,
+            },
+            Shape::Polygon(points) => {
+                Shape::Polygon(points.iter().map(|point| point + by).collect())
+            }
+        }
+    }
+
+    /// Finds how this shape overlaps `other`, if it does. Shapes that only touch don't overlap.
+    ///
+    /// When the shapes' cores are apart, the normal runs between their closest points. When they
+    /// overlap, it's the direction that separates them the shortest distance, and when even that
+    /// is ambiguous, like two circles with the same centre, it's straight down.
+    pub fn contact(&self, other: &Shape) -> Option<Contact> {
+        let (core, other) = (self.get_core(), other.get_core());
+        let reach = core.radius + other.radius;
+        if let Some((from, to)) = core.get_closest_points(&other) {
+            let offset = to - from;
+            let distance = offset.norm();
+            return if distance < reach {
+                Some(Contact {
+                    normal: offset / distance,
+                    depth: reach - distance,
+                })
+            } else {
+                None
+            };
+        }
+
+        let mut nearest: Option<(f32, Vector2<f32>)> = None;
+        for axis in core.get_axes().into_iter().chain(other.get_axes()) {
+            let (min, max) = core.project(axis);
+            let (other_min, other_max) = other.project(axis);
+            let (forward, backward) = (max - other_min, other_max - min);
+            let (overlap, normal) = if forward <= backward {
+                (forward, axis)
+            } else {
+                (backward, -axis)
+            };
+            if nearest.map_or(true, |(nearest, _)| overlap < nearest) {
+                nearest = Some((overlap, normal));
+            }
+        }
+        let (overlap, normal) = nearest.unwrap_or((0.0, Vector2::new(0.0, 1.0)));
+        let depth = overlap + reach;
+        if depth > 0.0 {
+            Some(Contact { normal, depth })
+        } else {
+            None
+        }
+    }
+
+    /// Finds when this shape, moving by `motion`, first touches `other`, if it does before the
+    /// end of its motion. A shape that already overlaps `other` only counts as a hit, at time 0,
+    /// when it's moving further into it.
+    ///
+    /// Circles against upright boxes are swept exactly. Everything else steps towards the other
+    /// shape by as far as it's sure not to hit it, until it's within `SWEEP_TOLERANCE`.
+    pub fn sweep(&self, motion: Vector2<f32>, other: &Shape) -> Option<Hit> {
+        if let (Shape::Circle(circle), Shape::Aabb { min, max }) = (self, other) {
+            let time = sweep_circle_aabb(circle, motion, *min, *max)?;
+            let center = circle.center + motion * time;
+            let offset = center - glm::clamp_vec(&center, min, max);
+            if offset.norm() > 0.0 {
+                return Some(Hit {
+                    time,
+                    normal: offset.normalize(),
+                });
+            }
+        }
+
+        if let Some(contact) = self.contact(other) {
+            return if contact.normal.dot(&motion) > 0.0 {
+                Some(Hit {
+                    time: 0.0,
+                    normal: -contact.normal,
+                })
+            } else {
+                None
+            };
+        }
+        let length = motion.norm();
+        if length == 0.0 {
+            return None;
+        }
+
+        let other = other.get_core();
+        let reach = self.get_core().radius + other.radius;
+        let mut time = 0.0;
+        for _ in 0..MAX_SWEEP_STEPS {
+            let moved = self.translate(motion * time).get_core();
+            // the cores can only meet inside the radii, which the loop never gets through
+            let (from, to) = moved.get_closest_points(&other)?;
+            let offset = to - from;
+            let distance = offset.norm();
+            let gap = distance - reach;
+            if gap < SWEEP_TOLERANCE {
+                let normal = -offset / distance;
+                return if normal.dot(&motion) < 0.0 {
+                    Some(Hit { time, normal })
+                } else {
+                    None
+                };
+            }
+            // stopping short of touching keeps the cores of shapes without a radius apart
+            time += (gap - SWEEP_TOLERANCE / 2.0) / length;
+            if time > 1.0 {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+impl Core {
+    /// Returns the directions the core could be separated from another along: the normals of
+    /// its edges, plus the direction of a single segment for when the other core is in line
+    /// with it.
+    fn get_axes(&self) -> Vec<Vector2<f32>> {
+        let points = &self.points;
+        let edges = match points.len() {
+            1 => 0,
+            2 => 1,
+            count => count,
+        };
+        let mut axes: Vec<_> = (0..edges)
+            .map(|i| points[(i + 1) % points.len()] - points[i])
+            .filter(|edge| edge.norm() > 0.0)
+            .map(|edge| Vector2::new(-edge[1], edge[0]).normalize())
+            .collect();
+        if edges == 1 && !axes.is_empty() {
+            axes.push(Vector2::new(axes[0][1], -axes[0][0]));
+        }
+        axes
+    }
+
+    fn project(&self, axis: Vector2<f32>) -> (f32, f32) {
+        self.points
+            .iter()
+            .map(|point| point.dot(&axis))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| {
+                (min.min(x), max.max(x))
+            })
+    }
+
+    /// Returns the point of the core nearest `point`, which has to be outside it.
+    fn get_closest_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        let points = &self.points;
+        if points.len() == 1 {
+            return points[0];
+        }
+        let edges = if points.len() == 2 { 1 } else { points.len() };
+        (0..edges)
+            .map(|i| get_closest_on_segment(point, points[i], points[(i + 1) % points.len()]))
+            .min_by(|a, b| (a - point).norm().total_cmp(&(b - point).norm()))
+            .unwrap()
+    }
+
+    /// Returns the closest points of this core and `other`, or `None` if they touch or overlap.
+    ///
+    /// The closest points of two convex shapes that are apart always include a corner of one of
+    /// them, so only the corners of each have to be checked against the other.
+    fn get_closest_points(&self, other: &Core) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        let axes = self.get_axes().into_iter().chain(other.get_axes());
+        let apart = if self.points.len() == 1 && other.points.len() == 1 {
+            self.points[0] != other.points[0]
+        } else {
+            axes.into_iter().any(|axis| {
+                let (min, max) = self.project(axis);
+                let (other_min, other_max) = other.project(axis);
+                max < other_min || other_max < min
+            })
+        };
+        if !apart {
+            return None;
+        }
+
+        let from_self = self
+            .points
+            .iter()
+            .map(|point| (*point, other.get_closest_point(*point)));
+        let from_other = other
+            .points
+            .iter()
+            .map(|point| (self.get_closest_point(*point), *point));
+        from_self
+            .chain(from_other)
+            .min_by(|(a, b), (c, d)| (b - a).norm().total_cmp(&(d - c).norm()))
+    }
+}
+
+/// Turns `vector` `angle` radians clockwise on screen.
+fn rotate(vector: Vector2<f32>, angle: f32) -> Vector2<f32> {
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(
+        vector[0] * cos - vector[1] * sin,
+        vector[0] * sin + vector[1] * cos,
+    )
+}
+
+fn get_closest_on_segment(
+    point: Vector2<f32>,
+    start: Vector2<f32>,
+    end: Vector2<f32>,
+) -> Vector2<f32> {
+    let along = end - start;
+    let length = along.dot(&along);
+    if length == 0.0 {
+        return start;
+    }
+    let t = ((point - start).dot(&along) / length).clamp(0.0, 1.0);
+    start + along * t
+}
+
+/// Finds the earliest time in `[0, 1]` at which a circle moving by `motion` touches
+/// the box from `min` to `max`.
+///
+/// This treats the circle as a point moving through the box grown by `radius`, which has rounded
+/// corners. A circle that already overlaps the box only counts as a hit, at time 0, when it's
+/// moving further into the box.
+pub fn sweep_circle_aabb(
+    circle: &Circle,
+    motion: Vector2<f32>,
+    min: Vector2<f32>,
+    max: Vector2<f32>,
+) -> Option<f32> {
+    let Circle { center, radius } = *circle;
+    let closest = glm::clamp_vec(&center, &min, &max);
+    let offset = closest - center;
+    if offset.norm() < radius {
+        return if offset.dot(&motion) > 0.0 {
+            Some(0.0)
+        } else {
+            None
+        };
+    }
+
+    let (grown_min, grown_max) = (min - Vector2::repeat(radius), max + Vector2::repeat(radius));
+    let (mut enter, mut exit) = (0.0f32, 1.0f32);
+    for i in 0..2 {
+        if motion[i] == 0.0 {
+            if center[i] < grown_min[i] || center[i] > grown_max[i] {
+                return None;
+            }
+        } else {
+            let near = (grown_min[i] - center[i]) / motion[i];
+            let far = (grown_max[i] - center[i]) / motion[i];
+            enter = enter.max(near.min(far));
+            exit = exit.min(near.max(far));
+            if enter > exit {
+                return None;
+            }
+        }
+    }
+
+    // if the point enters the grown box next to one of the corners, it has to hit the rounded
+    // corner instead
+    let point = center + motion * enter;
+    let corner = |i: usize| {
+        if point[i] < min[i] {
+            Some(min[i])
+        } else if point[i] > max[i] {
+            Some(max[i])
+        } else {
+            None
+        }
+    };
+    let time = match (corner(0), corner(1)) {
+        (Some(x), Some(y)) => sweep_point_circle(center, motion, [x, y].into(), radius)?,
+        _ => enter,
+    };
+
+    // grazing contact while moving away from the box isn't a hit
+    let contact = center + motion * time;
+    if (glm::clamp_vec(&contact, &min, &max) - contact).dot(&motion) > 0.0 {
+        Some(time)
+    } else {
+        None
+    }
+}
+
+fn sweep_point_circle(
+    point: Vector2<f32>,
+    motion: Vector2<f32>,
+    center: Vector2<f32>,
+    radius: f32,
+) -> Option<f32> {
+    let offset = point - center;
+    let a = motion.dot(&motion);
+    let b = 2.0 * motion.dot(&offset);
+    let c = offset.dot(&offset) - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let time = (-b - discriminant.sqrt()) / (2.0 * a);
+    if (0.0..=1.0).contains(&time) {
+        Some(time)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_4};
+
+    fn aabb(min: [f32; 2], max: [f32; 2]) -> Shape {
+        Shape::Aabb {
+            min: min.into(),
+            max: max.into(),
+        }
+    }
+
+    fn circle(center: [f32; 2], radius: f32) -> Shape {
+        Shape::Circle(Circle {
+            center: center.into(),
+            radius,
+        })
+    }
+
+    fn capsule(start: [f32; 2], end: [f32; 2], radius: f32) -> Shape {
+        Shape::Capsule {
+            start: start.into(),
+            end: end.into(),
+            radius,
+        }
+    }
+
+    fn polygon(points: &[[f32; 2]]) -> Shape {
+        Shape::Polygon(points.iter().map(|point| (*point).into()).collect())
+    }
+
+    fn assert_close(actual: Vector2<f32>, expected: [f32; 2]) {
+        assert!(
+            (actual - Vector2::from(expected)).norm() < 1e-3,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    /// Checks the contact both ways round, which should only flip the normal.
+    fn assert_contact(a: &Shape, b: &Shape, normal: [f32; 2], depth: f32) {
+        let contact = a.contact(b).expect("shapes don't overlap");
+        assert_close(contact.normal, normal);
+        assert!(
+            (contact.depth - depth).abs() < 1e-3,
+            "depth {} != {}",
+            contact.depth,
+            depth
+        );
+        let reverse = b
+            .contact(a)
+            .expect("shapes don't overlap the other way round");
+        assert_close(reverse.normal, [-normal[0], -normal[1]]);
+        assert!((reverse.depth - depth).abs() < 1e-3);
+    }
+
+    fn assert_apart(a: &Shape, b: &Shape) {
+        assert_eq!(a.contact(b), None);
+        assert_eq!(b.contact(a), None);
+    }
+
+    #[test]
+    fn boxes_overlap_along_the_shallower_axis() {
+        let a = aabb([0.0, 0.0], [10.0, 10.0]);
+        assert_contact(&a, &aabb([8.0, 1.0], [18.0, 9.0]), [1.0, 0.0], 2.0);
+        assert_contact(&a, &aabb([1.0, -7.0], [9.0, 3.0]), [0.0, -1.0], 3.0);
+        // corner to corner, deeper across than down
+        assert_contact(&a, &aabb([7.0, 9.0], [17.0, 19.0]), [0.0, 1.0], 1.0);
+        // one inside the other
+        assert_contact(&a, &aabb([1.0, 4.0], [3.0, 6.0]), [-1.0, 0.0], 3.0);
+    }
+
+    #[test]
+    fn touching_shapes_do_not_overlap() {
+        let a = aabb([0.0, 0.0], [10.0, 10.0]);
+        assert_apart(&a, &aabb([10.0, 0.0], [20.0, 10.0]));
+        assert_apart(&a, &aabb([10.0, 10.0], [20.0, 20.0]));
+        assert_apart(&a, &circle([15.0, 5.0], 5.0));
+        assert_apart(&circle([0.0, 0.0], 1.0), &circle([2.0, 0.0], 1.0));
+        assert_apart(&a, &capsule([0.0, 12.0], [10.0, 12.0], 2.0));
+    }
+
+    #[test]
+    fn circles_overlap_along_the_line_between_them() {
+        assert_contact(
+            &circle([0.0, 0.0], 5.0),
+            &circle([6.0, 8.0], 6.0),
+            [0.6, 0.8],
+            1.0,
+        );
+        assert_apart(&circle([0.0, 0.0], 5.0), &circle([8.0, 8.0], 5.0));
+    }
+
+    #[test]
+    fn circles_with_the_same_centre_push_apart_vertically() {
+        let contact = circle([3.0, 3.0], 2.0)
+            .contact(&circle([3.0, 3.0], 1.0))
+            .unwrap();
+        assert_close(contact.normal, [0.0, 1.0]);
+        assert_eq!(contact.depth, 3.0);
+    }
+
+    #[test]
+    fn circles_hit_box_edges_square_on() {
+        let a = aabb([0.0, 0.0], [100.0, 40.0]);
+        assert_contact(&circle([50.0, -4.0], 5.0), &a, [0.0, 1.0], 1.0);
+        assert_contact(&circle([50.0, 43.0], 5.0), &a, [0.0, -1.0], 2.0);
+        assert_contact(&circle([-2.0, 20.0], 5.0), &a, [1.0, 0.0], 3.0);
+        assert_contact(&circle([104.0, 20.0], 5.0), &a, [-1.0, 0.0], 1.0);
+    }
+
+    #[test]
+    fn circles_hit_box_corners_diagonally() {
+        let a = aabb([0.0, 0.0], [100.0, 40.0]);
+        assert_contact(
+            &circle([-3.0, -3.0], 5.0),
+            &a,
+            [FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+            5.0 - 18f32.sqrt(),
+        );
+        assert_contact(&circle([103.0, 44.0], 6.0), &a, [-0.6, -0.8], 1.0);
+        // inside the corner of the grown box, but outside the rounded corner
+        assert_apart(&circle([-4.0, -4.0], 5.0), &a);
+    }
+
+    #[test]
+    fn circle_centres_on_box_edges_and_corners() {
+        let a = aabb([0.0, 0.0], [100.0, 40.0]);
+        // the centre sits exactly on the edge, so there's no direction between closest points
+        assert_contact(&circle([50.0, 0.0], 5.0), &a, [0.0, 1.0], 5.0);
+        assert_contact(&circle([100.0, 20.0], 5.0), &a, [-1.0, 0.0], 5.0);
+        let corner = circle([0.0, 0.0], 5.0).contact(&a).unwrap();
+        assert_eq!(corner.depth, 5.0);
+        assert!(corner.normal == Vector2::new(1.0, 0.0) || corner.normal == Vector2::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn circles_inside_boxes_leave_by_the_nearest_edge() {
+        let a = aabb([0.0, 0.0], [100.0, 40.0]);
+        assert_contact(&circle([50.0, 10.0], 5.0), &a, [0.0, 1.0], 15.0);
+        assert_contact(&circle([95.0, 20.0], 2.0), &a, [-1.0, 0.0], 7.0);
+    }
+
+    #[test]
+    fn oriented_boxes_turn() {
+        // a square turned 45 degrees, poking its bottom corner into the top of a box
+        let diamond = Shape::Obb {
+            center: [50.0, -6.0].into(),
+            half_size: Vector2::repeat(5.0),
+            rotation: FRAC_PI_4,
+        };
+        let a = aabb([0.0, 0.0], [100.0, 40.0]);
+        assert_contact(&diamond, &a, [0.0, 1.0], 50f32.sqrt() - 6.0);
+
+        // the same square upright doesn't reach
+        let square = Shape::Obb {
+            center: [50.0, -6.0].into(),
+            half_size: Vector2::repeat(5.0),
+            rotation: 0.0,
+        };
+        assert_apart(&square, &a);
+
+        // its corner is clear of a circle off to the side that the upright square would hit
+        let off_corner = circle([55.5, -0.5], 1.0);
+        assert_apart(&diamond, &off_corner);
+        assert!(square.contact(&off_corner).is_some());
+    }
+
+    #[test]
+    fn capsules_have_round_ends_and_flat_sides() {
+        let a = capsule([0.0, 0.0], [20.0, 0.0], 5.0);
+        assert_contact(&a, &circle([10.0, 8.0], 4.0), [0.0, 1.0], 1.0);
+        assert_contact(&a, &circle([28.0, 0.0], 4.0), [1.0, 0.0], 1.0);
+        assert_contact(&a, &circle([26.0, 8.0], 6.0), [0.6, 0.8], 1.0);
+        assert_apart(&a, &circle([27.0, 7.0], 4.0));
+        assert_contact(&a, &aabb([-10.0, 4.0], [30.0, 10.0]), [0.0, 1.0], 1.0);
+    }
+
+    #[test]
+    fn capsules_in_line_with_each_other() {
+        let a = capsule([0.0, 0.0], [10.0, 0.0], 1.0);
+        assert_apart(&a, &capsule([13.0, 0.0], [20.0, 0.0], 1.0));
+        assert_contact(&a, &capsule([11.5, 0.0], [20.0, 0.0], 1.0), [1.0, 0.0], 0.5);
+        // crossing cores
+        let crossed = capsule([5.0, -10.0], [5.0, 10.0], 1.0).contact(&a).unwrap();
+        // either way across is as short as the other
+        assert_eq!(crossed.depth, 7.0);
+        assert_eq!(crossed.normal[0].abs(), 1.0);
+    }
+
+    #[test]
+    fn polygons_work_in_either_winding_order() {
+        let clockwise = polygon(&[[0.0, 0.0], [10.0, 10.0], [0.0, 10.0]]);
+        let anticlockwise = polygon(&[[0.0, 0.0], [0.0, 10.0], [10.0, 10.0]]);
+        // a circle against the sloping edge
+        let slope = [FRAC_1_SQRT_2, -FRAC_1_SQRT_2];
+        for triangle in [&clockwise, &anticlockwise] {
+            assert_contact(triangle, &circle([7.0, 3.0], 3.0), slope, 3.0 - 8f32.sqrt());
+            assert_contact(triangle, &aabb([-5.0, 9.0], [15.0, 20.0]), [0.0, 1.0], 1.0);
+            assert_apart(triangle, &circle([8.0, 2.0], 2.0));
+        }
+    }
+
+    #[test]
+    fn boxes_match_the_polygon_of_their_corners() {
+        let a = aabb([0.0, 0.0], [10.0, 10.0]);
+        let corners = polygon(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]);
+        for other in [
+            circle([12.0, 5.0], 3.0),
+            circle([12.0, 12.0], 4.0),
+            aabb([9.0, -5.0], [20.0, 3.0]),
+        ] {
+            assert_eq!(a.contact(&other), corners.contact(&other));
+        }
+    }
+
+    #[test]
+    fn colliders_are_fitted_to_the_transform() {
+        let mut transform = Transform::new([10.0, 20.0], [40.0, 20.0]);
+        assert_eq!(
+            Collider::Aabb.get_shape(&transform),
+            aabb([10.0, 20.0], [50.0, 40.0])
+        );
+        assert_eq!(
+            Collider::Circle.get_shape(&transform),
+            circle([30.0, 30.0], 10.0)
+        );
+        assert_eq!(
+            Collider::Capsule.get_shape(&transform),
+            capsule([20.0, 30.0], [40.0, 30.0], 10.0)
+        );
+
+        transform.rotation = FRAC_PI_4 * 2.0;
+        match Collider::Capsule.get_shape(&transform) {
+            Shape::Capsule { start, end, .. } => {
+                assert_close(start, [30.0, 20.0]);
+                assert_close(end, [30.0, 40.0]);
+            }
+            shape => panic!("{:?} isn't a capsule", shape),
+        }
+        // upright boxes ignore the rotation
+        assert_eq!(
+            Collider::Aabb.get_shape(&transform),
+            aabb([10.0, 20.0], [50.0, 40.0])
+        );
+        match Collider::Polygon(vec![[0.0, 0.0].into(), [1.0, 0.5].into()]).get_shape(&transform) {
+            Shape::Polygon(points) => {
+                assert_close(points[0], [40.0, 10.0]);
+                assert_close(points[1], [30.0, 50.0]);
+            }
+            shape => panic!("{:?} isn't a polygon", shape),
+        }
+    }
+
+    #[test]
+    fn swept_circles_do_not_tunnel_through_thin_boxes() {
+        // a 2 pixel thick box, crossed many times over in one step
+        let (min, max) = (Vector2::new(0.0, 100.0), Vector2::new(200.0, 102.0));
+        let ball = Circle {
+            center: [100.0, 0.0].into(),
+            radius: 5.0,
+        };
+        let time = sweep_circle_aabb(&ball, [0.0, 1000.0].into(), min, max).unwrap();
+        assert!((time - 0.095).abs() < 1e-5);
+        // and at a steep angle, past where the ball starts and ends up either side of it
+        let time = sweep_circle_aabb(&ball, [50.0, 1000.0].into(), min, max).unwrap();
+        assert!((time - 0.095).abs() < 1e-5);
+        let time = sweep_circle_aabb(&ball, [0.0, -1000.0].into(), min, max);
+        assert_eq!(time, None);
+    }
+
+    #[test]
+    fn swept_circles_stop_where_they_first_touch() {
+        let (min, max) = (Vector2::new(0.0, 0.0), Vector2::new(100.0, 40.0));
+        let cases = [
+            // from each side, square on
+            ([50.0, -20.0], [0.0, 40.0], 0.375),
+            ([50.0, 60.0], [0.0, -40.0], 0.375),
+            ([-20.0, 20.0], [40.0, 0.0], 0.375),
+            ([120.0, 20.0], [-40.0, 0.0], 0.375),
+            // at an angle onto the top edge
+            ([20.0, -25.0], [40.0, 40.0], 0.5),
+            // into the rounded corner, 3/5 of the radius to the side of it
+            ([-3.0, -20.0], [0.0, 40.0], 0.4),
+        ];
+        for (center, motion, expected) in cases.iter() {
+            let ball = Circle {
+                center: Vector2::from(*center),
+                radius: 5.0,
+            };
+            let motion = Vector2::from(*motion);
+            let time = sweep_circle_aabb(&ball, motion, min, max).unwrap();
+            assert!((time - expected).abs() < 1e-5, "{:?}: {}", center, time);
+
+            // just touching the box at that time
+            let center = ball.center + motion * time;
+            let distance = (glm::clamp_vec(&center, &min, &max) - center).norm();
+            assert!(
+                (distance - 5.0).abs() < 1e-4,
+                "{:?}: {}",
+                ball.center,
+                distance
+            );
+        }
+
+        // missing the corner, and not moving at all
+        let ball = Circle {
+            center: [-5.0, -20.0].into(),
+            radius: 5.0,
+        };
+        assert_eq!(sweep_circle_aabb(&ball, [0.0, 40.0].into(), min, max), None);
+        assert_eq!(sweep_circle_aabb(&ball, Vector2::zeros(), min, max), None);
+    }
+
+    #[test]
+    fn swept_circles_hit_box_edges() {
+        let a = aabb([0.0, 0.0], [100.0, 40.0]);
+        let ball = circle([50.0, -20.0], 5.0);
+        let hit = ball.sweep([0.0, 30.0].into(), &a).unwrap();
+        assert!((hit.time - 0.5).abs() < 1e-5);
+        assert_close(hit.normal, [0.0, -1.0]);
+
+        // too short, moving away, and passing alongside
+        assert_eq!(ball.sweep([0.0, 10.0].into(), &a), None);
+        assert_eq!(ball.sweep([0.0, -30.0].into(), &a), None);
+        assert_eq!(
+            circle([-5.0, -20.0], 5.0).sweep([0.0, 100.0].into(), &a),
+            None
+        );
+    }
+
+    #[test]
+    fn swept_circles_hit_box_corners() {
+        let a = aabb([0.0, 0.0], [100.0, 40.0]);
+        // heading straight down just inside the corner of the grown box
+        let hit = circle([-3.0, -20.0], 5.0)
+            .sweep([0.0, 40.0].into(), &a)
+            .unwrap();
+        assert_close(hit.normal, [-0.6, -0.8]);
+        assert!((hit.time - 0.4).abs() < 1e-5);
+
+        // diagonally into the corner
+        let hit = circle([-10.0, -10.0], 2.0)
+            .sweep([20.0, 20.0].into(), &a)
+            .unwrap();
+        assert_close(hit.normal, [-FRAC_1_SQRT_2, -FRAC_1_SQRT_2]);
+    }
+
+    #[test]
+    fn overlapping_shapes_only_hit_when_moving_further_in() {
+        let a = aabb([0.0, 0.0], [100.0, 40.0]);
+        let ball = circle([50.0, -2.0], 5.0);
+        let hit = ball.sweep([0.0, 1.0].into(), &a).unwrap();
+        assert_eq!(hit.time, 0.0);
+        assert_close(hit.normal, [0.0, -1.0]);
+        assert_eq!(ball.sweep([0.0, -1.0].into(), &a), None);
+
+        let square = Shape::Obb {
+            center: [50.0, -2.0].into(),
+            half_size: Vector2::repeat(5.0),
+            rotation: 0.1,
+        };
+        assert_eq!(square.sweep([0.0, 1.0].into(), &a).unwrap().time, 0.0);
+        assert_eq!(square.sweep([0.0, -1.0].into(), &a), None);
+    }
+
+    #[test]
+    fn other_shapes_sweep_to_within_tolerance() {
+        let a = aabb([0.0, 0.0], [100.0, 40.0]);
+        let square = Shape::Obb {
+            center: [50.0, -20.0].into(),
+            half_size: Vector2::repeat(5.0),
+            rotation: FRAC_PI_4,
+        };
+        let hit = square.sweep([0.0, 40.0].into(), &a).unwrap();
+        let expected = (20.0 - 50f32.sqrt()) / 40.0;
+        assert!((hit.time - expected).abs() < 1e-4, "{}", hit.time);
+        assert_close(hit.normal, [0.0, -1.0]);
+
+        // a capsule sliding along a box doesn't hit it
+        let rod = capsule([-50.0, -5.0], [-20.0, -5.0], 5.0);
+        assert_eq!(rod.sweep([200.0, 0.0].into(), &a), None);
+        // but one sliding into its side does, end first
+        let rod = capsule([-50.0, 20.0], [-20.0, 20.0], 5.0);
+        let hit = rod.sweep([50.0, 0.0].into(), &a).unwrap();
+        assert!((hit.time - 0.3).abs() < 1e-4);
+        assert_close(hit.normal, [-1.0, 0.0]);
+
+        assert_eq!(rod.sweep(Vector2::zeros(), &a), None);
+    }
+}
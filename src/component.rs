@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use nalgebra::{Vector2, Vector4};
+
+use crate::animation::AnimationPlayer;
+use crate::renderer::{DrawParams, Layer, Renderer};
+
+/// Where an entity is and how big it is.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    /// The top-left corner of the entity's box.
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    /// How far the entity is turned around its centre, in radians clockwise. Only colliders that
+    /// turn with the transform collide as turned.
+    pub rotation: f32,
+    /// How much bigger the entity is drawn than its box, around its centre. This only changes how
+    /// the entity looks, not what it collides with.
+    pub scale: Vector2<f32>,
+}
+
+impl Transform {
+    pub fn new(position: impl Into<Vector2<f32>>, size: impl Into<Vector2<f32>>) -> Self {
+        Transform {
+            position: position.into(),
+            size: size.into(),
+            rotation: 0.0,
+            scale: Vector2::repeat(1.0),
+        }
+    }
+
+    pub fn get_center(&self) -> Vector2<f32> {
+        self.position + self.size / 2.0
+    }
+
+    /// Returns the bottom-right corner of the entity's box.
+    pub fn get_max(&self) -> Vector2<f32> {
+        self.position + self.size
+    }
+}
+
+/// How an entity is drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sprite {
+    /// The name of the texture, which themes can swap.
+    pub name: String,
+    /// Multiplies the colour of the sprite, with the last component multiplying its alpha.
+    pub tint: Vector4<f32>,
+    pub layer: Layer,
+    /// Drawn instead of the texture while there is one.
+    pub animation: Option<AnimationPlayer>,
+    /// The brick code whose colour in the theme the sprite is drawn in, instead of its tint. The
+    /// sprite isn't drawn at all if the theme has no colour for the code.
+    pub palette: Option<u32>,
+}
+
+impl Sprite {
+    /// An untinted sprite on the entities layer.
+    pub fn new(name: impl Into<String>) -> Self {
+        Sprite {
+            name: name.into(),
+            tint: Vector4::repeat(1.0),
+            layer: Layer::Entities,
+            animation: None,
+            palette: None,
+        }
+    }
+
+    pub fn with_layer(self, layer: Layer) -> Self {
+        Sprite { layer, ..self }
+    }
+
+    pub fn with_animation(self, animation: AnimationPlayer) -> Self {
+        Sprite {
+            animation: Some(animation),
+            ..self
+        }
+    }
+
+    pub fn with_palette(self, code: u32) -> Self {
+        Sprite {
+            palette: Some(code),
+            ..self
+        }
+    }
+
+    /// Returns how the sprite is drawn over `transform`, scaled and turned around its centre.
+    pub fn get_draw_params(&self, transform: &Transform) -> DrawParams {
+        let size = transform.size;
+        let scaled = size.component_mul(&transform.scale);
+        let position = transform.position + (size - scaled) / 2.0;
+        DrawParams {
+            rotation: transform.rotation,
+            tint: self.tint,
+            layer: self.layer,
+            ..DrawParams::new(position, scaled)
+        }
+    }
+
+    /// Draws the animation if the sprite has one, or the texture if it doesn't or the animation
+    /// is missing.
+    pub fn draw(&self, renderer: &mut dyn Renderer, params: &DrawParams) {
+        let params = match self.palette {
+            Some(code) => match renderer.get_theme().get_brick_color(code) {
+                Some(color) => params.with_color(color),
+                None => return,
+            },
+            None => *params,
+        };
+        let animated = match &self.animation {
+            Some(player) => renderer.draw_animation(player, &params),
+            None => false,
+        };
+        if !animated {
+            renderer.draw_sprite(&self.name, &params);
+        }
+    }
+}
+
+/// How fast an entity is moving, in pixels per second.
+pub type Velocity = Vector2<f32>;
+
+/// How long an entity has left before it's despawned.
+pub type Lifetime = Duration;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprites_are_scaled_around_their_centre() {
+        let mut transform = Transform::new([10.0, 20.0], [100.0, 40.0]);
+        transform.scale = [0.5, 2.0].into();
+        let params = Sprite::new("ball").get_draw_params(&transform);
+        assert_eq!(params.position, Vector2::new(35.0, 0.0));
+        assert_eq!(params.size, Vector2::new(50.0, 80.0));
+        assert_eq!(transform.get_center(), Vector2::new(60.0, 40.0));
+    }
+}
@@ -0,0 +1,484 @@
+use std::time::Duration;
+
+use nalgebra::Vector2;
+
+use crate::collision::{Collider, Contact, Hit, Shape};
+use crate::component::{Lifetime, Sprite, Transform, Velocity};
+use crate::renderer::Renderer;
+
+/// Identifies an entity in a `World`.
+///
+/// The slot of a despawned entity is reused with a new generation, so ids kept after an entity
+/// is despawned stop matching anything rather than picking up whatever was spawned in its place.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntityId {
+    index: usize,
+    generation: u32,
+}
+
+impl EntityId {
+    fn new(index: usize, generation: u32) -> Self {
+        EntityId { index, generation }
+    }
+}
+
+/// One kind of component for the entities of a world, indexed by their ids.
+pub struct Storage<T> {
+    slots: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Storage { slots: Vec::new() }
+    }
+}
+
+impl<T> Storage<T> {
+    /// Gives `id` the component, returning the one it had before, if any.
+    pub fn insert(&mut self, id: EntityId, component: T) -> Option<T> {
+        if id.index >= self.slots.len() {
+            self.slots.resize_with(id.index + 1, || None);
+        }
+        let old = self.remove(id);
+        self.slots[id.index] = Some((id.generation, component));
+        old
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        match self.slots.get_mut(id.index) {
+            Some(slot)
+                if slot
+                    .as_ref()
+                    .map_or(false, |(generation, _)| *generation == id.generation) =>
+            {
+                slot.take().map(|(_, component)| component)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        match self.slots.get(id.index) {
+            Some(Some((generation, component))) if *generation == id.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        match self.slots.get_mut(id.index) {
+            Some(Some((generation, component))) if *generation == id.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    /// Iterates over the entities that have the component, in `EntityId` order.
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref()
+                .map(|(generation, component)| (EntityId::new(index, *generation), component))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.as_mut()
+                    .map(|(generation, component)| (EntityId::new(index, *generation), component))
+            })
+    }
+
+    /// Lists the entities that have the component, so the world can be changed while going
+    /// through them.
+    pub fn ids(&self) -> Vec<EntityId> {
+        self.iter().map(|(id, _)| id).collect()
+    }
+}
+
+/// Game logic attached to an entity, like a brick shrinking away once it's broken.
+pub trait Behaviour {
+    /// Runs once per tick, before anything in the world moves. The behaviour is taken out of the
+    /// world while it runs, so it can change anything else, including despawning its entity.
+    ///
+    /// Returns whether the behaviour carries on. One that returns `false` is dropped.
+    fn update(&mut self, id: EntityId, world: &mut World, delta: Duration) -> bool;
+}
+
+/// Everything in play, stored as components and moved on by systems.
+///
+/// An entity is only an id; what it is comes from the components it's given. Everything is kept
+/// and visited in `EntityId` order so that the systems stay deterministic.
+#[derive(Default)]
+pub struct World {
+    /// The current generation of each slot, and whether an entity is using it.
+    entities: Vec<(u32, bool)>,
+    /// The slots of despawned entities, to be reused.
+    free: Vec<usize>,
+
+    pub transforms: Storage<Transform>,
+    pub sprites: Storage<Sprite>,
+    pub colliders: Storage<Collider>,
+    pub velocities: Storage<Velocity>,
+    /// Entities that move by sweeping along their path and bouncing off whatever they hit, so
+    /// that fast ones can't pass through anything. The movement system leaves them to whatever
+    /// bounces them, which finds what's in their way with `sweep`.
+    pub swept: Storage<()>,
+    pub lifetimes: Storage<Lifetime>,
+    pub behaviours: Storage<Box<dyn Behaviour>>,
+}
+
+impl World {
+    /// Adds an entity without any components.
+    pub fn spawn(&mut self) -> EntityId {
+        match self.free.pop() {
+            Some(index) => {
+                let (generation, alive) = &mut self.entities[index];
+                *generation += 1;
+                *alive = true;
+                EntityId::new(index, *generation)
+            }
+            None => {
+                self.entities.push((0, true));
+                EntityId::new(self.entities.len() - 1, 0)
+            }
+        }
+    }
+
+    /// Removes an entity and all of its components. Despawning an entity that's already gone
+    /// does nothing.
+    pub fn despawn(&mut self, id: EntityId) {
+        if !self.is_alive(id) {
+            return;
+        }
+        self.entities[id.index].1 = false;
+        self.free.push(id.index);
+        self.transforms.remove(id);
+        self.sprites.remove(id);
+        self.colliders.remove(id);
+        self.velocities.remove(id);
+        self.swept.remove(id);
+        self.lifetimes.remove(id);
+        self.behaviours.remove(id);
+    }
+
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.entities.get(id.index) == Some(&(id.generation, true))
+    }
+
+    /// Runs one tick of the systems: behaviours, then movement, then lifetimes, then animations.
+    pub fn update(&mut self, delta: Duration) {
+        self.run_behaviours(delta);
+        self.move_entities(delta);
+        self.expire(delta);
+        self.animate(delta);
+    }
+
+    pub fn run_behaviours(&mut self, delta: Duration) {
+        for id in self.behaviours.ids() {
+            if let Some(mut behaviour) = self.behaviours.remove(id) {
+                let carry_on = behaviour.update(id, self, delta);
+                // unless the behaviour despawned its entity or swapped itself for another
+                if carry_on && self.is_alive(id) && self.behaviours.get(id).is_none() {
+                    self.behaviours.insert(id, behaviour);
+                }
+            }
+        }
+    }
+
+    /// Moves every entity with a velocity and a transform, apart from swept ones.
+    pub fn move_entities(&mut self, delta: Duration) {
+        let millis = delta.as_millis() as f32;
+        for (id, velocity) in self.velocities.iter() {
+            if self.swept.get(id).is_some() {
+                continue;
+            }
+            if let Some(transform) = self.transforms.get_mut(id) {
+                transform.position += velocity * millis / 1000.0;
+            }
+        }
+    }
+
+    /// Counts down lifetimes, despawning the entities whose time is up.
+    pub fn expire(&mut self, delta: Duration) {
+        let mut expired = Vec::new();
+        for (id, lifetime) in self.lifetimes.iter_mut() {
+            *lifetime = lifetime.checked_sub(delta).unwrap_or_default();
+            if *lifetime == Duration::from_millis(0) {
+                expired.push(id);
+            }
+        }
+        for id in expired {
+            self.despawn(id);
+        }
+    }
+
+    pub fn animate(&mut self, delta: Duration) {
+        for (_, sprite) in self.sprites.iter_mut() {
+            if let Some(animation) = &mut sprite.animation {
+                animation.update(delta);
+            }
+        }
+    }
+
+    /// The entity's collider placed on its transform, if it has both.
+    pub fn get_shape(&self, id: EntityId) -> Option<Shape> {
+        Some(self.colliders.get(id)?.get_shape(self.transforms.get(id)?))
+    }
+
+    /// Finds when `shape`, moving by `motion`, first touches the collider of `other`, if it does
+    /// before the end of its motion. Entities without a collider can't be hit.
+    pub fn sweep(&self, shape: &Shape, motion: Vector2<f32>, other: EntityId) -> Option<Hit> {
+        shape.sweep(motion, &self.get_shape(other)?)
+    }
+
+    /// Finds every pair of entities whose colliders overlap, each pair in `EntityId` order with
+    /// the contact normal pointing from the first to the second.
+    ///
+    /// Every collider is checked against every other, which is fine for the handful of entities
+    /// that need it.
+    // the ball sweeps, so nothing in the game collides by overlapping yet
+    #[allow(dead_code)]
+    pub fn find_collisions(&self) -> Vec<(EntityId, EntityId, Contact)> {
+        let shapes: Vec<_> = self
+            .colliders
+            .ids()
+            .into_iter()
+            .filter_map(|id| Some((id, self.get_shape(id)?)))
+            .collect();
+        let mut collisions = Vec::new();
+        for (i, (a, shape)) in shapes.iter().enumerate() {
+            for (b, other) in shapes[i + 1..].iter() {
+                if let Some(contact) = shape.contact(other) {
+                    collisions.push((*a, *b, contact));
+                }
+            }
+        }
+        collisions
+    }
+
+    /// Draws every entity with a sprite and a transform.
+    pub fn render(&self, renderer: &mut dyn Renderer) {
+        for (id, sprite) in self.sprites.iter() {
+            if let Some(transform) = self.transforms.get(id) {
+                sprite.draw(renderer, &sprite.get_draw_params(transform));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::{Resources, TextureSettings};
+    use crate::software::SoftwareRenderer;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    /// Counts its updates and despawns its entity on the third.
+    struct Countdown(u32);
+
+    impl Behaviour for Countdown {
+        fn update(&mut self, id: EntityId, world: &mut World, _delta: Duration) -> bool {
+            self.0 += 1;
+            world
+                .velocities
+                .insert(id, [self.0 as f32 * 100.0, 0.0].into());
+            if self.0 == 3 {
+                world.despawn(id);
+            }
+            true
+        }
+    }
+
+    /// Runs once and stops.
+    struct Once;
+
+    impl Behaviour for Once {
+        fn update(&mut self, _id: EntityId, _world: &mut World, _delta: Duration) -> bool {
+            false
+        }
+    }
+
+    fn spawn_box(world: &mut World, position: [f32; 2]) -> EntityId {
+        let id = world.spawn();
+        world
+            .transforms
+            .insert(id, Transform::new(position, [10.0, 10.0]));
+        world.colliders.insert(id, Collider::Aabb);
+        id
+    }
+
+    #[test]
+    fn despawned_ids_go_stale() {
+        let mut world = World::default();
+        let a = world.spawn();
+        world
+            .transforms
+            .insert(a, Transform::new([0.0, 0.0], [1.0, 1.0]));
+        world.despawn(a);
+        assert!(!world.is_alive(a));
+        assert!(world.transforms.get(a).is_none());
+
+        // the slot is reused, but the old id doesn't see the new entity
+        let b = world.spawn();
+        world
+            .transforms
+            .insert(b, Transform::new([5.0, 5.0], [1.0, 1.0]));
+        assert_ne!(a, b);
+        assert!(world.transforms.get(a).is_none());
+        assert!(world.transforms.get_mut(a).is_none());
+        assert!(world.transforms.remove(a).is_none());
+        assert!(world.transforms.get(b).is_some());
+        world.despawn(a);
+        assert!(world.is_alive(b));
+    }
+
+    #[test]
+    fn behaviours_run_before_movement() {
+        let mut world = World::default();
+        let id = world.spawn();
+        world
+            .transforms
+            .insert(id, Transform::new([0.0, 0.0], [1.0, 1.0]));
+        world.behaviours.insert(id, Box::new(Countdown(0)));
+
+        world.update(TICK);
+        assert_eq!(world.transforms.get(id).unwrap().position[0], 1.0);
+        world.update(TICK);
+        assert_eq!(world.transforms.get(id).unwrap().position[0], 3.0);
+        world.update(TICK);
+        assert!(!world.is_alive(id));
+        assert!(world.behaviours.get(id).is_none());
+    }
+
+    #[test]
+    fn finished_behaviours_are_dropped() {
+        let mut world = World::default();
+        let id = world.spawn();
+        world.behaviours.insert(id, Box::new(Once));
+        world.update(TICK);
+        assert!(world.is_alive(id));
+        assert!(world.behaviours.get(id).is_none());
+    }
+
+    #[test]
+    fn swept_entities_are_left_to_move_themselves() {
+        let mut world = World::default();
+        let (moved, swept) = (
+            spawn_box(&mut world, [0.0, 0.0]),
+            spawn_box(&mut world, [0.0, 0.0]),
+        );
+        for id in [moved, swept].iter() {
+            world.velocities.insert(*id, [100.0, -50.0].into());
+        }
+        world.swept.insert(swept, ());
+
+        world.update(TICK);
+        assert_eq!(
+            world.transforms.get(moved).unwrap().position,
+            [1.0, -0.5].into()
+        );
+        assert_eq!(
+            world.transforms.get(swept).unwrap().position,
+            [0.0, 0.0].into()
+        );
+    }
+
+    #[test]
+    fn lifetimes_run_out() {
+        let mut world = World::default();
+        let short = world.spawn();
+        world.lifetimes.insert(short, Duration::from_millis(15));
+        let long = world.spawn();
+        world.lifetimes.insert(long, Duration::from_millis(30));
+
+        world.update(TICK);
+        assert!(world.is_alive(short) && world.is_alive(long));
+        world.update(TICK);
+        assert!(!world.is_alive(short) && world.is_alive(long));
+        world.update(TICK);
+        assert!(!world.is_alive(long));
+    }
+
+    #[test]
+    fn collisions_are_found_in_id_order() {
+        let mut world = World::default();
+        let a = spawn_box(&mut world, [0.0, 0.0]);
+        let b = spawn_box(&mut world, [5.0, 5.0]);
+        let c = spawn_box(&mut world, [12.0, 0.0]);
+        let d = spawn_box(&mut world, [100.0, 100.0]);
+        world.colliders.remove(d);
+        world
+            .transforms
+            .insert(d, Transform::new([0.0, 0.0], [100.0, 100.0]));
+        let pairs: Vec<_> = world
+            .find_collisions()
+            .into_iter()
+            .map(|(a, b, _)| (a, b))
+            .collect();
+        assert_eq!(pairs, [(a, b), (b, c)]);
+    }
+
+    #[test]
+    fn sweeps_only_hit_colliders() {
+        let mut world = World::default();
+        let wall = spawn_box(&mut world, [100.0, 0.0]);
+        let ghost = world.spawn();
+        world
+            .transforms
+            .insert(ghost, Transform::new([50.0, 0.0], [10.0, 10.0]));
+
+        let shape = Shape::Aabb {
+            min: [0.0, 0.0].into(),
+            max: [10.0, 10.0].into(),
+        };
+        let motion = Vector2::new(200.0, 0.0);
+        let hit = world.sweep(&shape, motion, wall).unwrap();
+        assert!((hit.time - 0.45).abs() < 1e-4);
+        assert_eq!(hit.normal[0], -1.0);
+        assert!(world.sweep(&shape, motion, ghost).is_none());
+    }
+
+    #[test]
+    fn renders_entities_with_a_sprite_and_a_transform() {
+        let mut resources = Resources::default();
+        let white = RgbaImage::from_pixel(1, 1, Rgba([255; 4]));
+        resources.load_image(
+            "blank",
+            DynamicImage::ImageRgba8(white),
+            TextureSettings::default(),
+        );
+        resources.pack();
+
+        let mut world = World::default();
+        let drawn = world.spawn();
+        world
+            .transforms
+            .insert(drawn, Transform::new([0.0, 0.0], [2.0, 2.0]));
+        world
+            .sprites
+            .insert(drawn, Sprite::new("blank").with_palette(2));
+        // nowhere to draw this one
+        let lost = world.spawn();
+        world.sprites.insert(lost, Sprite::new("blank"));
+        // and no colour in the theme to draw this one in
+        let uncoloured = world.spawn();
+        world
+            .transforms
+            .insert(uncoloured, Transform::new([4.0, 0.0], [2.0, 2.0]));
+        world
+            .sprites
+            .insert(uncoloured, Sprite::new("blank").with_palette(0));
+
+        let mut renderer = SoftwareRenderer::new(&resources);
+        renderer.begin_frame();
+        world.render(&mut renderer);
+        renderer.end_frame();
+        let pixel = |x, y| renderer.get_image().get_pixel(x, y).data;
+        assert_eq!(pixel(1, 1), [51, 153, 255, 255]);
+        assert_eq!(pixel(2, 2), [0, 0, 0, 255]);
+        assert_eq!(pixel(5, 1), [0, 0, 0, 255]);
+    }
+}
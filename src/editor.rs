@@ -0,0 +1,629 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use glium::glutin::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
+use nalgebra::{Vector2, Vector3};
+
+use crate::input::{Input, InputState};
+use crate::level::{get_brick_sprite, Level, LevelData, BRICK_CODES};
+use crate::play::PlayScene;
+use crate::renderer::{Layer, Renderer};
+use crate::scene::{Scene, Transition};
+use crate::{GAME_HEIGHT, GAME_WIDTH};
+
+const MAX_GRID_SIZE: usize = 64;
+/// How many edits can be undone.
+const MAX_UNDO: usize = 100;
+const SWATCH_SIZE: [f32; 2] = [64.0, 32.0];
+const SWATCH_SPACING: f32 = 16.0;
+
+pub enum EditorAction {
+    None,
+    TestPlay,
+}
+
+pub struct Editor {
+    data: LevelData,
+    path: PathBuf,
+    brush: u32,
+    hover: Option<(usize, usize)>,
+    painting: bool,
+    /// The level as it was before each edit, most recent last.
+    history: Vec<LevelData>,
+}
+
+impl Editor {
+    /// Opens the level at `path` for editing, or starts a blank level if it doesn't exist yet.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let data = if path.exists() {
+            let data = fs::read_to_string(&path)?;
+            LevelData::from_json(data)?
+        } else {
+            LevelData {
+                width: 15,
+                height: 8,
+                ..LevelData::default()
+            }
+        };
+
+        Ok(Editor::new(data, path))
+    }
+
+    fn new(data: LevelData, path: PathBuf) -> Self {
+        let mut editor = Editor {
+            data,
+            path,
+            brush: 1,
+            hover: None,
+            painting: false,
+            history: Vec::new(),
+        };
+        let (columns, rows) = (editor.get_columns(), editor.get_rows());
+        editor.resize(columns, rows);
+        editor.history.clear();
+        editor
+    }
+
+    pub fn build_level(&self) -> Level {
+        Level::from_data(&self.data)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(&self.path, self.data.to_json())
+    }
+
+    fn get_columns(&self) -> usize {
+        self.data.width as usize
+    }
+
+    fn get_rows(&self) -> usize {
+        self.data.height as usize
+    }
+
+    /// Keeps `data` as the level before an edit, so the edit can be undone.
+    fn checkpoint(&mut self, data: LevelData) {
+        if self.history.len() == MAX_UNDO {
+            self.history.remove(0);
+        }
+        self.history.push(data);
+    }
+
+    fn undo(&mut self) {
+        if let Some(data) = self.history.pop() {
+            self.data = data;
+            self.hover = None;
+            self.painting = false;
+        }
+    }
+
+    /// Resizes the grid, keeping the bricks in the cells that are left. Losing any bricks is
+    /// warned about, since only undoing brings them back. Grids with more cells than the gaps and
+    /// margins leave room for are refused.
+    fn resize(&mut self, columns: usize, rows: usize) {
+        let columns = columns.clamp(1, MAX_GRID_SIZE);
+        let rows = rows.clamp(1, MAX_GRID_SIZE);
+        let before = self.data.clone();
+        self.data.width = columns as u32;
+        self.data.height = rows as u32;
+        if !self.data.has_room_for_bricks() {
+            eprintln!(
+                "there's no room for a {}x{} grid with the level's gaps and margins",
+                columns, rows
+            );
+            self.data = before;
+            return;
+        }
+        self.data.map.resize(rows, Vec::new());
+        for row in self.data.map.iter_mut() {
+            row.resize(columns, 0);
+        }
+        self.hover = None;
+
+        let count = |map: &[Vec<u32>]| map.iter().flatten().filter(|cell| **cell != 0).count();
+        let lost = count(&before.map) - count(&self.data.map);
+        if lost > 0 {
+            eprintln!(
+                "resizing the grid removed {} bricks, press Ctrl+Z to bring them back",
+                lost
+            );
+        }
+        if self.data != before {
+            self.checkpoint(before);
+        }
+    }
+
+    fn get_cell_at(&self, point: Vector2<f32>) -> Option<(usize, usize)> {
+        let pitch = self.data.get_pitch();
+        let point = point - self.data.get_origin();
+        if point[0] < 0.0 || point[1] < 0.0 {
+            return None;
+        }
+        let (row, column) = (
+            (point[1] / pitch[1]) as usize,
+            (point[0] / pitch[0]) as usize,
+        );
+        if row < self.get_rows() && column < self.get_columns() {
+            Some((row, column))
+        } else {
+            None
+        }
+    }
+
+    fn get_swatch_position(&self, code: u32) -> Vector2<f32> {
+        let count = BRICK_CODES as f32;
+        let width = count * SWATCH_SIZE[0] + (count - 1.0) * SWATCH_SPACING;
+        let left = (GAME_WIDTH as f32 - width) / 2.0;
+        [
+            left + code as f32 * (SWATCH_SIZE[0] + SWATCH_SPACING),
+            GAME_HEIGHT as f32 - SWATCH_SIZE[1] * 2.0,
+        ]
+        .into()
+    }
+
+    fn get_swatch_at(&self, point: Vector2<f32>) -> Option<u32> {
+        (0..BRICK_CODES).find(|code| {
+            let position = self.get_swatch_position(*code);
+            point[0] >= position[0]
+                && point[0] < position[0] + SWATCH_SIZE[0]
+                && point[1] >= position[1]
+                && point[1] < position[1] + SWATCH_SIZE[1]
+        })
+    }
+
+    fn paint(&mut self) {
+        if let Some((row, column)) = self.hover {
+            self.data.map[row][column] = self.brush;
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event, pointer: Option<Vector2<f32>>) -> EditorAction {
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return EditorAction::None,
+        };
+        match event {
+            WindowEvent::CursorMoved { .. } => {
+                self.hover = pointer.and_then(|pointer| self.get_cell_at(pointer));
+                if self.painting {
+                    self.paint();
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.painting = false;
+                if *state == ElementState::Pressed {
+                    if let Some(code) = pointer.and_then(|pointer| self.get_swatch_at(pointer)) {
+                        self.brush = code;
+                    } else if self.hover.is_some() {
+                        self.checkpoint(self.data.clone());
+                        self.painting = true;
+                        self.paint();
+                    }
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => {
+                if let Some((row, column)) = self.hover {
+                    self.checkpoint(self.data.clone());
+                    let cell = &mut self.data.map[row][column];
+                    *cell = (*cell + 1) % BRICK_CODES;
+                }
+            }
+            WindowEvent::KeyboardInput { input, .. } if input.state == ElementState::Pressed => {
+                let (columns, rows) = (self.get_columns(), self.get_rows());
+                match input.virtual_keycode {
+                    Some(VirtualKeyCode::Left) => self.resize(columns - 1, rows),
+                    Some(VirtualKeyCode::Right) => self.resize(columns + 1, rows),
+                    Some(VirtualKeyCode::Up) => self.resize(columns, rows - 1),
+                    Some(VirtualKeyCode::Down) => self.resize(columns, rows + 1),
+                    Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::F5) => {
+                        return EditorAction::TestPlay;
+                    }
+                    Some(VirtualKeyCode::Z) if input.modifiers.ctrl => self.undo(),
+                    Some(VirtualKeyCode::S) if input.modifiers.ctrl => match self.save() {
+                        Ok(()) => eprintln!("saved level to {}", self.path.display()),
+                        Err(err) => eprintln!("failed to save {}: {}", self.path.display(), err),
+                    },
+                    Some(code) => {
+                        if let Some(brush) = get_brush_for_key(code) {
+                            self.brush = brush;
+                        }
+                    }
+                    None => (),
+                }
+            }
+            _ => (),
+        }
+        EditorAction::None
+    }
+
+    pub fn render(&self, renderer: &mut dyn Renderer) {
+        let size = self.data.get_brick_size();
+        let (columns, rows) = (self.get_columns(), self.get_rows());
+
+        for (i, row) in self.data.map.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if let Some(color) = renderer.get_theme().get_brick_color(*cell) {
+                    let position = self.data.get_brick_position(i, j);
+                    renderer.render_sprite_by_name(
+                        Layer::Bricks,
+                        get_brick_sprite(*cell),
+                        position,
+                        size,
+                        color,
+                    );
+                }
+            }
+        }
+        for brick in self.data.bricks.iter() {
+            if let Some(color) = renderer.get_theme().get_brick_color(brick.code) {
+                let position = [brick.x, brick.y];
+                let size = [brick.width, brick.height];
+                renderer.render_sprite_by_name(
+                    Layer::Bricks,
+                    get_brick_sprite(brick.code),
+                    position,
+                    size,
+                    color,
+                );
+            }
+        }
+
+        // grid lines run along the left and top edges of each cell, plus the far edges of the
+        // last row and column
+        let grid_color = [0.5, 0.5, 0.5];
+        let origin = self.data.get_origin();
+        let end = self.data.get_brick_position(rows - 1, columns - 1) + size;
+        let grid_size = end - origin;
+        for j in 0..=columns {
+            let x = if j < columns {
+                self.data.get_brick_position(0, j)[0]
+            } else {
+                end[0]
+            };
+            let position = [x, origin[1]];
+            renderer.render_sprite_by_name(
+                Layer::Effects,
+                "blank",
+                position,
+                [1.0, grid_size[1]],
+                grid_color,
+            );
+        }
+        for i in 0..=rows {
+            let y = if i < rows {
+                self.data.get_brick_position(i, 0)[1]
+            } else {
+                end[1]
+            };
+            let position = [origin[0], y];
+            renderer.render_sprite_by_name(
+                Layer::Effects,
+                "blank",
+                position,
+                [grid_size[0], 1.0],
+                grid_color,
+            );
+        }
+
+        if let Some((row, column)) = self.hover {
+            let position = self.data.get_brick_position(row, column);
+            render_outline(renderer, Layer::Effects, position, size, [1.0, 1.0, 1.0]);
+        }
+
+        for code in 0..BRICK_CODES {
+            let position = self.get_swatch_position(code);
+            match renderer.get_theme().get_brick_color(code) {
+                Some(color) => renderer.render_sprite_by_name(
+                    Layer::Ui,
+                    get_brick_sprite(code),
+                    position,
+                    SWATCH_SIZE,
+                    color,
+                ),
+                None => renderer.render_sprite_by_name(
+                    Layer::Ui,
+                    "blank",
+                    position,
+                    SWATCH_SIZE,
+                    [0.1, 0.1, 0.1],
+                ),
+            }
+            if code == self.brush {
+                render_outline(renderer, Layer::Ui, position, SWATCH_SIZE, [1.0, 1.0, 0.0]);
+            }
+        }
+    }
+}
+
+/// The editor as a scene. Enter or F5 puts test play on top of it, and F5 again comes back to the
+/// level as it was left.
+pub struct EditorScene {
+    editor: Editor,
+    /// What test play seeds its randomness with.
+    seed: u64,
+}
+
+impl EditorScene {
+    pub fn new(editor: Editor, seed: u64) -> Self {
+        EditorScene { editor, seed }
+    }
+}
+
+impl Scene for EditorScene {
+    fn get_name(&self) -> &str {
+        "editor"
+    }
+
+    /// The mouse button might be let go during test play, so painting stops on the way out.
+    fn on_exit(&mut self) {
+        self.editor.painting = false;
+    }
+
+    fn handle_input(&mut self, event: &Event, input: &InputState) -> Transition {
+        match self.editor.handle_event(event, input.get_pointer()) {
+            EditorAction::None => Transition::None,
+            EditorAction::TestPlay => {
+                let level = self.editor.build_level();
+                Transition::Push(Box::new(PlayScene::test_play(level, self.seed)))
+            }
+        }
+    }
+
+    fn update(&mut self, _input: &Input, _delta: Duration) -> Transition {
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        renderer.render_background();
+        self.editor.render(renderer);
+    }
+}
+
+impl fmt::Display for EditorScene {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "editing: {}", self.editor.path.display())
+    }
+}
+
+fn get_brush_for_key(code: VirtualKeyCode) -> Option<u32> {
+    use glium::glutin::VirtualKeyCode::*;
+    let brush = match code {
+        Key0 | Numpad0 => 0,
+        Key1 | Numpad1 => 1,
+        Key2 | Numpad2 => 2,
+        Key3 | Numpad3 => 3,
+        Key4 | Numpad4 => 4,
+        Key5 | Numpad5 => 5,
+        _ => return None,
+    };
+    Some(brush)
+}
+
+fn render_outline(
+    renderer: &mut dyn Renderer,
+    layer: Layer,
+    position: impl Into<Vector2<f32>>,
+    size: impl Into<Vector2<f32>>,
+    color: impl Into<Vector3<f32>>,
+) {
+    let (position, size, color) = (position.into(), size.into(), color.into());
+    let thickness = 2.0;
+    renderer.render_sprite_by_name(layer, "blank", position, [size[0], thickness], color);
+    renderer.render_sprite_by_name(
+        layer,
+        "blank",
+        [position[0], position[1] + size[1] - thickness],
+        [size[0], thickness],
+        color,
+    );
+    renderer.render_sprite_by_name(layer, "blank", position, [thickness, size[1]], color);
+    renderer.render_sprite_by_name(
+        layer,
+        "blank",
+        [position[0] + size[0] - thickness, position[1]],
+        [thickness, size[1]],
+        color,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glium::glutin::{DeviceId, KeyboardInput, ModifiersState, WindowId};
+
+    use crate::game::TICK;
+    use crate::scene::SceneStack;
+
+    fn editor(width: u32, height: u32) -> Editor {
+        let data = LevelData {
+            width,
+            height,
+            ..LevelData::default()
+        };
+        Editor::new(data, PathBuf::from("level.json"))
+    }
+
+    fn window_event(event: WindowEvent) -> Event {
+        Event::WindowEvent {
+            // only compared against, never handed back to the windowing system
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        }
+    }
+
+    fn press(key: VirtualKeyCode, ctrl: bool) -> Event {
+        window_event(WindowEvent::KeyboardInput {
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput {
+                scancode: 0,
+                state: ElementState::Pressed,
+                virtual_keycode: Some(key),
+                modifiers: ModifiersState {
+                    ctrl,
+                    ..ModifiersState::default()
+                },
+            },
+        })
+    }
+
+    fn click(button: MouseButton, state: ElementState) -> Event {
+        window_event(WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state,
+            button,
+            modifiers: ModifiersState::default(),
+        })
+    }
+
+    /// Points at the middle of a cell and paints it with `brush`.
+    fn paint(editor: &mut Editor, row: usize, column: usize, brush: u32) {
+        let size = editor.data.get_brick_size();
+        let point = editor.data.get_brick_position(row, column) + size / 2.0;
+        editor.hover = editor.get_cell_at(point);
+        editor.brush = brush;
+        editor.handle_event(&click(MouseButton::Left, ElementState::Pressed), None);
+        editor.handle_event(&click(MouseButton::Left, ElementState::Released), None);
+    }
+
+    #[test]
+    fn finds_the_cell_under_the_pointer() {
+        let editor = editor(4, 2);
+        let size = editor.data.get_brick_size();
+        let cell = |row, column| editor.data.get_brick_position(row, column);
+        assert_eq!(editor.get_cell_at(cell(0, 0)), Some((0, 0)));
+        assert_eq!(editor.get_cell_at(cell(1, 3) + size / 2.0), Some((1, 3)));
+        assert_eq!(editor.get_cell_at(cell(1, 3) + size), None);
+        assert_eq!(
+            editor.get_cell_at(cell(0, 0) - Vector2::new(1.0, 0.0)),
+            None
+        );
+        assert_eq!(
+            editor.get_cell_at(cell(0, 0) - Vector2::new(0.0, 1.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn paints_and_cycles_cells() {
+        let mut editor = editor(4, 2);
+        paint(&mut editor, 1, 2, 3);
+        assert_eq!(editor.data.map, [[0, 0, 0, 0], [0, 0, 3, 0]]);
+        assert!(!editor.painting);
+
+        // right-clicking cycles through the codes, wrapping back round to empty
+        for code in [4, 5, 0].iter() {
+            editor.handle_event(&click(MouseButton::Right, ElementState::Pressed), None);
+            assert_eq!(editor.data.map[1][2], *code);
+        }
+
+        editor.handle_event(&press(VirtualKeyCode::Key2, false), None);
+        assert_eq!(editor.brush, 2);
+        editor.handle_event(&press(VirtualKeyCode::Z, true), None);
+        assert_eq!(editor.data.map[1][2], 5);
+    }
+
+    #[test]
+    fn resizes_the_grid() {
+        let mut editor = editor(3, 3);
+        paint(&mut editor, 0, 0, 2);
+        paint(&mut editor, 2, 2, 3);
+
+        editor.resize(4, 2);
+        assert_eq!((editor.data.width, editor.data.height), (4, 2));
+        assert_eq!(editor.data.map, [[2, 0, 0, 0], [0, 0, 0, 0]]);
+
+        // undoing brings back the bricks that were cut off
+        editor.handle_event(&press(VirtualKeyCode::Z, true), None);
+        assert_eq!(editor.data.map, [[2, 0, 0], [0, 0, 0], [0, 0, 3]]);
+
+        editor.resize(0, MAX_GRID_SIZE + 1);
+        assert_eq!(editor.data.width, 1);
+        assert_eq!(editor.data.map.len(), MAX_GRID_SIZE);
+        // resizing to the size the grid already is isn't an edit
+        let edits = editor.history.len();
+        editor.handle_event(&press(VirtualKeyCode::Left, false), None);
+        assert_eq!(editor.history.len(), edits);
+
+        // nor is resizing to more cells than there's room for
+        editor.data.gap = [0.0, 10.0];
+        editor.resize(1, 39);
+        assert_eq!(editor.data.height, 39);
+        editor.resize(1, 40);
+        assert_eq!(editor.data.height, 39);
+        assert!(editor.data.has_room_for_bricks());
+    }
+
+    #[test]
+    fn test_play_goes_over_the_editor() {
+        let names = |stack: &SceneStack| {
+            stack
+                .iter()
+                .map(|scene| scene.get_name().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let mut input = InputState::default();
+        let mut stack = SceneStack::default();
+        let mut editor = editor(4, 2);
+        paint(&mut editor, 0, 0, 2);
+        stack.push(Box::new(EditorScene::new(editor, 1)));
+
+        stack.handle_input(&press(VirtualKeyCode::Return, false), &mut input);
+        assert_eq!(names(&stack), ["editor", "play"]);
+        stack.update(&Input::default(), TICK);
+        assert!(stack
+            .iter()
+            .last()
+            .unwrap()
+            .to_string()
+            .contains("1/1 remaining"));
+
+        // escape pauses test play like any other game, rather than leaving it
+        let pause = Input {
+            pause: true,
+            ..Input::default()
+        };
+        stack.update(&pause, TICK);
+        assert_eq!(names(&stack), ["editor", "play", "pause"]);
+        stack.update(&pause, TICK);
+        assert_eq!(names(&stack), ["editor", "play"]);
+
+        stack.handle_input(&press(VirtualKeyCode::F5, false), &mut input);
+        assert_eq!(names(&stack), ["editor"]);
+        stack.update(&Input::default(), TICK);
+        stack.handle_input(&press(VirtualKeyCode::F5, false), &mut input);
+        assert_eq!(names(&stack), ["editor", "play"]);
+    }
+
+    #[test]
+    fn saves_what_the_level_reads() {
+        let mut editor = editor(5, 3);
+        editor.data.gap = [4.0, 2.0];
+        editor.data.theme = Some("night".to_owned());
+        editor.data.bricks.push(crate::level::FreeBrick {
+            x: 10.0,
+            y: 500.0,
+            width: 60.0,
+            height: 20.0,
+            code: 1,
+        });
+        paint(&mut editor, 0, 4, 2);
+        paint(&mut editor, 2, 0, 1);
+
+        let data = LevelData::from_json(editor.data.to_json()).unwrap();
+        assert_eq!(data, editor.data);
+        let level = editor.build_level();
+        assert_eq!(level.bricks().count(), 3);
+        assert_eq!(level.count_destructible(), 1);
+    }
+}
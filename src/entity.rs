@@ -1,22 +1,25 @@
-use glium::Texture2d;
-use nalgebra::{Vector2, Vector3};
+use nalgebra::Vector2;
 
-use crate::sprite::SpriteRenderer;
+use crate::component::Transform;
+use crate::ecs::{EntityId, World};
 
+/// A game object whose components, like where it is, how it's drawn and the shape it collides
+/// as, are kept in a `World`.
 pub trait Entity {
-    fn get_sprite(&self) -> &str;
-    fn get_position(&self) -> Vector2<f32>;
-    fn get_size(&self) -> Vector2<f32>;
+    fn get_id(&self) -> EntityId;
 
-    fn get_color(&self) -> Vector3<f32> {
-        [1.0, 1.0, 1.0].into()
+    fn get_transform<'a>(&self, world: &'a World) -> &'a Transform {
+        world
+            .transforms
+            .get(self.get_id())
+            .expect("entity has no transform")
     }
 
-    fn render(&self, renderer: &mut SpriteRenderer) {
-        let sprite = self.get_sprite();
-        let position = self.get_position();
-        let size = self.get_size();
-        let color = self.get_color();
-        renderer.render_sprite_by_name(sprite, position, size, color);
+    fn get_position(&self, world: &World) -> Vector2<f32> {
+        self.get_transform(world).position
+    }
+
+    fn get_size(&self, world: &World) -> Vector2<f32> {
+        self.get_transform(world).size
     }
 }
@@ -0,0 +1,141 @@
+use image::{Rgba, RgbaImage};
+
+use crate::atlas::Region;
+
+/// The name the font image is packed under.
+pub const FONT: &str = "font";
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+/// The gap left after each glyph, both in the font image and between characters of text.
+const SPACING: u32 = 1;
+
+/// A small pixel font covering what the game needs to say: digits, capital letters and some
+/// punctuation. Each glyph is drawn as rows of `#`s.
+#[rustfmt::skip]
+const GLYPHS: &[(char, [&str; GLYPH_HEIGHT as usize])] = &[
+    (' ', ["     ", "     ", "     ", "     ", "     ", "     ", "     "]),
+    ('0', [" ### ", "#   #", "#  ##", "# # #", "##  #", "#   #", " ### "]),
+    ('1', ["  #  ", " ##  ", "  #  ", "  #  ", "  #  ", "  #  ", " ### "]),
+    ('2', [" ### ", "#   #", "    #", "   # ", "  #  ", " #   ", "#####"]),
+    ('3', ["#####", "   # ", "  #  ", "   # ", "    #", "#   #", " ### "]),
+    ('4', ["   # ", "  ## ", " # # ", "#  # ", "#####", "   # ", "   # "]),
+    ('5', ["#####", "#    ", "#### ", "    #", "    #", "#   #", " ### "]),
+    ('6', ["  ## ", " #   ", "#    ", "#### ", "#   #", "#   #", " ### "]),
+    ('7', ["#####", "    #", "   # ", "  #  ", " #   ", " #   ", " #   "]),
+    ('8', [" ### ", "#   #", "#   #", " ### ", "#   #", "#   #", " ### "]),
+    ('9', [" ### ", "#   #", "#   #", " ####", "    #", "   # ", " ##  "]),
+    ('A', [" ### ", "#   #", "#   #", "#####", "#   #", "#   #", "#   #"]),
+    ('B', ["#### ", "#   #", "#   #", "#### ", "#   #", "#   #", "#### "]),
+    ('C', [" ### ", "#   #", "#    ", "#    ", "#    ", "#   #", " ### "]),
+    ('D', ["#### ", "#   #", "#   #", "#   #", "#   #", "#   #", "#### "]),
+    ('E', ["#####", "#    ", "#    ", "#### ", "#    ", "#    ", "#####"]),
+    ('F', ["#####", "#    ", "#    ", "#### ", "#    ", "#    ", "#    "]),
+    ('G', [" ### ", "#   #", "#    ", "# ###", "#   #", "#   #", " ####"]),
+    ('H', ["#   #", "#   #", "#   #", "#####", "#   #", "#   #", "#   #"]),
+    ('I', [" ### ", "  #  ", "  #  ", "  #  ", "  #  ", "  #  ", " ### "]),
+    ('J', ["  ###", "   # ", "   # ", "   # ", "   # ", "#  # ", " ##  "]),
+    ('K', ["#   #", "#  # ", "# #  ", "##   ", "# #  ", "#  # ", "#   #"]),
+    ('L', ["#    ", "#    ", "#    ", "#    ", "#    ", "#    ", "#####"]),
+    ('M', ["#   #", "## ##", "# # #", "# # #", "#   #", "#   #", "#   #"]),
+    ('N', ["#   #", "#   #", "##  #", "# # #", "#  ##", "#   #", "#   #"]),
+    ('O', [" ### ", "#   #", "#   #", "#   #", "#   #", "#   #", " ### "]),
+    ('P', ["#### ", "#   #", "#   #", "#### ", "#    ", "#    ", "#    "]),
+    ('Q', [" ### ", "#   #", "#   #", "#   #", "# # #", "#  # ", " ## #"]),
+    ('R', ["#### ", "#   #", "#   #", "#### ", "# #  ", "#  # ", "#   #"]),
+    ('S', [" ####", "#    ", "#    ", " ### ", "    #", "    #", "#### "]),
+    ('T', ["#####", "  #  ", "  #  ", "  #  ", "  #  ", "  #  ", "  #  "]),
+    ('U', ["#   #", "#   #", "#   #", "#   #", "#   #", "#   #", " ### "]),
+    ('V', ["#   #", "#   #", "#   #", "#   #", "#   #", " # # ", "  #  "]),
+    ('W', ["#   #", "#   #", "#   #", "# # #", "# # #", "# # #", " # # "]),
+    ('X', ["#   #", "#   #", " # # ", "  #  ", " # # ", "#   #", "#   #"]),
+    ('Y', ["#   #", "#   #", " # # ", "  #  ", "  #  ", "  #  ", "  #  "]),
+    ('Z', ["#####", "    #", "   # ", "  #  ", " #   ", "#    ", "#####"]),
+    ('.', ["     ", "     ", "     ", "     ", "     ", " ##  ", " ##  "]),
+    (',', ["     ", "     ", "     ", "     ", " ##  ", "  #  ", " #   "]),
+    (':', ["     ", " ##  ", " ##  ", "     ", " ##  ", " ##  ", "     "]),
+    ('-', ["     ", "     ", "     ", "#####", "     ", "     ", "     "]),
+    ('+', ["     ", "  #  ", "  #  ", "#####", "  #  ", "  #  ", "     "]),
+    ('/', ["     ", "    #", "   # ", "  #  ", " #   ", "#    ", "     "]),
+    ('!', ["  #  ", "  #  ", "  #  ", "  #  ", "  #  ", "     ", "  #  "]),
+    ('?', [" ### ", "#   #", "    #", "   # ", "  #  ", "     ", "  #  "]),
+    ('\'', ["  #  ", "  #  ", " #   ", "     ", "     ", "     ", "     "]),
+    ('(', ["   # ", "  #  ", " #   ", " #   ", " #   ", "  #  ", "   # "]),
+    (')', [" #   ", "  #  ", "   # ", "   # ", "   # ", "  #  ", " #   "]),
+];
+
+/// Draws every glyph side by side in white on a transparent background, in the order of
+/// `GLYPHS`.
+pub fn font_image() -> RgbaImage {
+    let width = GLYPHS.len() as u32 * (GLYPH_WIDTH + SPACING) - SPACING;
+    let mut image = RgbaImage::new(width, GLYPH_HEIGHT);
+    for (i, (_, rows)) in GLYPHS.iter().enumerate() {
+        let left = i as u32 * (GLYPH_WIDTH + SPACING);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, pixel) in row.chars().enumerate() {
+                if pixel == '#' {
+                    image.put_pixel(left + x as u32, y as u32, Rgba([255; 4]));
+                }
+            }
+        }
+    }
+    image
+}
+
+/// Returns the part of `font`, the region that `font_image` was packed into, that holds the
+/// glyph for `c`. Lowercase letters use the capitals, and anything else the font doesn't have
+/// is drawn as a question mark.
+pub fn get_glyph(font: Region, c: char) -> Region {
+    let c = c.to_ascii_uppercase();
+    let index = GLYPHS
+        .iter()
+        .position(|(glyph, _)| *glyph == c)
+        .or_else(|| GLYPHS.iter().position(|(glyph, _)| *glyph == '?'))
+        .unwrap();
+
+    let width = (GLYPHS.len() as u32 * (GLYPH_WIDTH + SPACING) - SPACING) as f32;
+    let scale = (font.max[0] - font.min[0]) / width;
+    let left = font.min[0] + (index as u32 * (GLYPH_WIDTH + SPACING)) as f32 * scale;
+    Region {
+        page: font.page,
+        min: [left, font.min[1]],
+        max: [left + GLYPH_WIDTH as f32 * scale, font.max[1]],
+    }
+}
+
+/// Returns how far apart characters are drawn in text that's `height` tall.
+pub fn get_advance(height: f32) -> f32 {
+    (GLYPH_WIDTH + SPACING) as f32 * height / GLYPH_HEIGHT as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_are_the_right_size() {
+        for (c, rows) in GLYPHS.iter() {
+            for row in rows.iter() {
+                assert_eq!(row.len(), GLYPH_WIDTH as usize, "'{}' has a bad row", c);
+            }
+        }
+    }
+
+    #[test]
+    fn glyphs_are_found_by_character() {
+        let font = Region {
+            page: 2,
+            min: [0.0, 0.5],
+            max: [1.0, 1.0],
+        };
+        let width = font_image().width() as f32;
+        let a = get_glyph(font, 'A');
+        assert_eq!(a.page, 2);
+        assert!((a.min[0] - 11.0 * 6.0 / width).abs() < 1e-6);
+        assert!((a.max[0] - (11.0 * 6.0 + 5.0) / width).abs() < 1e-6);
+        assert_eq!((a.min[1], a.max[1]), (0.5, 1.0));
+        assert_eq!(get_glyph(font, 'a'), a);
+        assert_eq!(get_glyph(font, '~'), get_glyph(font, '?'));
+    }
+}
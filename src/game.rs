@@ -1,166 +1,91 @@
-use std::collections::HashMap;
+use std::fmt;
 use std::time::Duration;
 
-use glium::glutin::{Event, VirtualKeyCode};
-use glium::{Display, Frame, Texture2d};
-use nalgebra::{Matrix4, Orthographic3, Vector2, Vector3};
+use glium::glutin::Event;
 
-use crate::ball::Ball;
-use crate::entity::Entity;
+use crate::editor::{Editor, EditorScene};
+use crate::input::{Input, InputState};
 use crate::level::Level;
-use crate::player::Player;
-use crate::resources::Resources;
-use crate::sprite::SpriteRenderer;
+use crate::play::PlayScene;
+use crate::renderer::Renderer;
+use crate::scene::{Scene, SceneStack};
 
-const BACKGROUND_IMAGE: &[u8] = include_bytes!("../textures/background.jpg");
-const PADDLE_IMAGE: &[u8] = include_bytes!("../textures/paddle.png");
-const BLOCK_IMAGE: &[u8] = include_bytes!("../textures/block.png");
-const BLOCK_SOLID_IMAGE: &[u8] = include_bytes!("../textures/block_solid.png");
-const BALL_IMAGE: &[u8] = include_bytes!("../textures/ball.png");
-const SPRITE_VERT: &str = include_str!("../shaders/sprite.vs");
-const SPRITE_FRAG: &str = include_str!("../shaders/sprite.fs");
+/// The length of one simulation step. The simulation always advances by exactly this much so
+/// that replays and headless runs behave identically to interactive play.
+pub const TICK: Duration = Duration::from_millis(10);
 
 const LEVEL_1: &str = include_str!("../levels/level1.json");
 const LEVEL_2: &str = include_str!("../levels/level2.json");
 const LEVEL_3: &str = include_str!("../levels/level3.json");
 const LEVEL_4: &str = include_str!("../levels/level4.json");
 
-pub struct Game<'a> {
-    pub resources: Resources,
-    pub display: &'a Display,
-    pub levels: Vec<Level>,
-    keymap: HashMap<VirtualKeyCode, bool>,
-    state: GameState,
-    level: usize,
-
-    player: Player,
-    ball: Ball,
+/// The levels that are compiled into the binary.
+pub fn default_levels() -> Vec<Level> {
+    vec![
+        Level::from_json(LEVEL_1),
+        Level::from_json(LEVEL_2),
+        Level::from_json(LEVEL_3),
+        Level::from_json(LEVEL_4),
+    ]
 }
 
-impl<'a> Game<'a> {
-    pub fn new(display: &'a Display) -> Self {
-        let mut resources = Resources::default();
-        resources
-            .load_image_from_memory(display, "background", &BACKGROUND_IMAGE, false)
-            .unwrap();
-        resources
-            .load_image_from_memory(display, "paddle", &PADDLE_IMAGE, true)
-            .unwrap();
-        resources
-            .load_image_from_memory(display, "ball", &BALL_IMAGE, true)
-            .unwrap();
-        resources
-            .load_image_from_memory(display, "block", &BLOCK_IMAGE, false)
-            .unwrap();
-        resources
-            .load_image_from_memory(display, "block_solid", &BLOCK_SOLID_IMAGE, false)
-            .unwrap();
-        resources.load_shader(display, "sprite", &SPRITE_VERT, &SPRITE_FRAG);
-
-        let levels = vec![
-            Level::from_json(&LEVEL_1),
-            Level::from_json(&LEVEL_2),
-            Level::from_json(&LEVEL_3),
-            Level::from_json(&LEVEL_4),
-        ];
-
-        let player = Player::new();
-        let ball = Ball::new(player.get_position());
-
-        Game {
-            resources,
-            display,
-            levels,
-            keymap: HashMap::new(),
-            state: GameState::Active,
-            level: 0,
-
-            player,
-            ball,
-        }
-    }
+/// The whole game: the scenes it's showing, and the clock and seed they share.
+pub struct Game {
+    scenes: SceneStack,
+    seed: u64,
+    tick: u64,
+}
 
-    pub fn get_current_level(&self) -> &Level {
-        self.levels.iter().nth(self.level).unwrap()
+impl Game {
+    /// Starts playing `levels` from `level`.
+    pub fn new(levels: Vec<Level>, level: usize, seed: u64) -> Self {
+        Game::with_scene(Box::new(PlayScene::new(levels, level, seed)), seed)
     }
 
-    pub fn get_current_level_mut(&mut self) -> &mut Level {
-        self.levels.iter_mut().nth(self.level).unwrap()
+    /// Starts the game in the level editor.
+    pub fn with_editor(editor: Editor, seed: u64) -> Self {
+        Game::with_scene(Box::new(EditorScene::new(editor, seed)), seed)
     }
 
-    pub fn get_renderer<'b>(&self, target: &'b mut Frame) -> SpriteRenderer<'b, '_> {
-        let program = self.resources.get_shader("sprite").unwrap();
-        SpriteRenderer::new(self, target)
+    fn with_scene(scene: Box<dyn Scene>, seed: u64) -> Self {
+        let mut scenes = SceneStack::default();
+        scenes.push(scene);
+        Game {
+            scenes,
+            seed,
+            tick: 0,
+        }
     }
 
-    pub fn handle_event(&mut self, event: Event) {
-        use glium::glutin::{ElementState, WindowEvent};
-        match event {
-            Event::WindowEvent {
-                event: WindowEvent::KeyboardInput { input, .. },
-                ..
-            } => {
-                if let Some(code) = input.virtual_keycode {
-                    self.keymap.insert(
-                        code,
-                        match &input.state {
-                            ElementState::Pressed => true,
-                            ElementState::Released => false,
-                        },
-                    );
-                }
-            }
-            _ => (),
+    /// Swaps in a new set of levels for the scene the game started with.
+    pub fn reload_levels(&mut self, levels: Vec<Level>) {
+        if let Some(scene) = self.scenes.get_bottom_mut() {
+            scene.reload_levels(levels);
         }
     }
 
-    fn is_key_pressed(&self, key: &VirtualKeyCode) -> bool {
-        self.keymap.get(key).cloned().unwrap_or_else(|| false)
+    /// Handles window events for the parts of the game that aren't simulated, like the editor.
+    pub fn handle_event(&mut self, event: &Event, input: &mut InputState) {
+        self.scenes.handle_input(event, input);
     }
 
-    pub fn update(&mut self, delta: Duration) {
-        match &self.state {
-            GameState::Active => {
-                let mut move_by = 0.0;
-                if self.is_key_pressed(&VirtualKeyCode::Left) {
-                    move_by = self.player.move_left(delta);
-                } else if self.is_key_pressed(&VirtualKeyCode::Right) {
-                    move_by = self.player.move_right(delta);
-                }
-                self.ball.move_by(move_by);
-
-                if self.is_key_pressed(&VirtualKeyCode::Space) {
-                    self.ball.unstick();
-                }
-                self.ball.update_position(delta);
-
-                let position = self.ball.get_position();
-                let radius = self.ball.get_radius();
-                let level = self.get_current_level_mut();
-                level.perform_collisions(position, radius);
-            }
-            GameState::Menu => {}
-            GameState::Win => {}
-        }
+    pub fn update(&mut self, input: &Input, delta: Duration) {
+        self.tick += 1;
+        self.scenes.update(input, delta);
     }
 
-    pub fn render(&self, renderer: &mut SpriteRenderer) {
-        renderer.render_sprite_by_name("background", [0.0, 0.0], [1024.0, 768.0], [1.0, 1.0, 1.0]);
-        match &self.state {
-            GameState::Active => {
-                let level = self.get_current_level();
-                level.render(renderer);
-                self.player.render(renderer);
-                self.ball.render(renderer);
-            }
-            GameState::Menu => {}
-            GameState::Win => {}
-        }
+    pub fn render(&self, renderer: &mut dyn Renderer) {
+        self.scenes.render(renderer);
     }
 }
 
-enum GameState {
-    Active,
-    Menu,
-    Win,
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "tick: {}", self.tick)?;
+        write!(f, "seed: {}", self.seed)?;
+        for scene in self.scenes.iter() {
+            write!(f, "\nscene: {}\n{}", scene.get_name(), scene)?;
+        }
+        Ok(())
+    }
 }
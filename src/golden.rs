@@ -0,0 +1,226 @@
+//! Screenshot tests that draw the game with the software renderer and compare the pictures
+//! against golden images in `tests/golden`.
+//!
+//! When a test fails, the picture it drew and a diff against the golden image are written to
+//! `target/golden`. After a deliberate change to how the game looks, check those pictures and
+//! then run the tests with `UPDATE_GOLDENS=1` to replace the golden images.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+
+use crate::assets;
+use crate::game::{self, Game, TICK};
+use crate::input::Input;
+use crate::play::PlayScene;
+use crate::renderer::Renderer;
+use crate::resources::Resources;
+use crate::scene::Scene;
+use crate::software::SoftwareRenderer;
+use crate::theme::DEFAULT_THEME;
+
+/// Goldens are drawn at half size to keep them small. Layout mistakes, like swapped rows and
+/// columns or textures upside down, show up just as well.
+const SCALE: f32 = 0.5;
+
+/// How far apart each channel of a pixel can be before the pixel counts as different, so that
+/// small rounding differences between platforms don't fail the tests.
+const TOLERANCE: u8 = 4;
+
+const SEED: u64 = 1;
+
+/// How long the breaking golden waits for the ball to hit a brick before giving up, so a change
+/// that makes it miss fails the test rather than hanging it.
+const MAX_BREAK_TICKS: usize = 1000;
+
+fn get_golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn get_output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden")
+}
+
+/// Returns how many pixels of `actual` are further than `tolerance` from `expected`, along with
+/// an image that shows them in red over a faded copy of `expected`. Images of different sizes
+/// differ everywhere.
+fn diff(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> (usize, RgbaImage) {
+    if actual.dimensions() != expected.dimensions() {
+        let (width, height) = actual.dimensions();
+        let image = RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 255]));
+        return ((width * height) as usize, image);
+    }
+
+    let mut count = 0;
+    let image = RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let (a, e) = (actual.get_pixel(x, y).data, expected.get_pixel(x, y).data);
+        let different = a
+            .iter()
+            .zip(e.iter())
+            .any(|(a, e)| (*a as i16 - *e as i16).abs() > tolerance as i16);
+        if different {
+            count += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let gray = ((e[0] as u32 + e[1] as u32 + e[2] as u32) / 3 / 4) as u8;
+            Rgba([gray, gray, gray, 255])
+        }
+    });
+    (count, image)
+}
+
+/// Compares `image` against the golden image called `name`, or replaces the golden image when
+/// `UPDATE_GOLDENS` is set.
+fn check(name: &str, image: &RgbaImage) {
+    let path = get_golden_dir().join(format!("{}.png", name));
+    if env::var_os("UPDATE_GOLDENS").is_some() {
+        fs::create_dir_all(get_golden_dir()).unwrap();
+        image.save(&path).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&path) {
+        Ok(expected) => expected.to_rgba(),
+        Err(err) => panic!(
+            "failed to load golden image {}: {}, run with UPDATE_GOLDENS=1 to create it",
+            path.display(),
+            err
+        ),
+    };
+    let (count, diff) = diff(image, &expected, TOLERANCE);
+    if count > 0 {
+        let output = get_output_dir();
+        fs::create_dir_all(&output).unwrap();
+        let (actual_path, diff_path) = (
+            output.join(format!("{}.png", name)),
+            output.join(format!("{}.diff.png", name)),
+        );
+        image.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{} pixels differ from {}, see {} and {}",
+            count,
+            path.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+fn render(resources: &Resources, theme: &str, draw: impl FnOnce(&mut dyn Renderer)) -> RgbaImage {
+    let mut renderer = SoftwareRenderer::with_scale(resources, SCALE);
+    renderer.begin_frame();
+    renderer.set_theme(theme);
+    draw(&mut renderer);
+    renderer.end_frame();
+    renderer.get_image().clone()
+}
+
+/// Plays `ticks` ticks of `level`, asking `script` for the input on each tick.
+fn play(level: usize, ticks: u64, mut script: impl FnMut(u64) -> Input) -> Game {
+    let mut game = Game::new(game::default_levels(), level, SEED);
+    for tick in 0..ticks {
+        game.update(&script(tick), TICK);
+    }
+    game
+}
+
+#[test]
+fn shipped_levels_match_goldens() {
+    let resources = assets::load_resources(None);
+    for level in 0..game::default_levels().len() {
+        let game = play(level, 0, |_| Input::default());
+        let image = render(&resources, DEFAULT_THEME, |renderer| game.render(renderer));
+        check(&format!("level{}", level + 1), &image);
+    }
+}
+
+#[test]
+fn mid_game_states_match_goldens() {
+    let resources = assets::load_resources(None);
+
+    // the ball in flight a second after launch
+    let game = play(0, 100, |tick| Input {
+        launch: tick == 0,
+        ..Input::default()
+    });
+    check(
+        "launched",
+        &render(&resources, DEFAULT_THEME, |renderer| game.render(renderer)),
+    );
+
+    // the paddle steered to the left with the ball aimed to the right
+    let game = play(1, 40, |_| Input {
+        left: true,
+        aim_right: true,
+        ..Input::default()
+    });
+    check(
+        "aiming",
+        &render(&resources, DEFAULT_THEME, |renderer| game.render(renderer)),
+    );
+
+    let game = play(2, 200, |tick| Input {
+        right: tick < 30,
+        launch: tick == 50,
+        ..Input::default()
+    });
+    check(
+        "night",
+        &render(&resources, "night", |renderer| game.render(renderer)),
+    );
+
+    // a brick halfway through breaking apart
+    let mut scene = PlayScene::new(game::default_levels(), 3, SEED);
+    let bricks = scene.get_current_level().count_remaining();
+    for tick in 0..MAX_BREAK_TICKS {
+        if scene.get_current_level().count_remaining() < bricks {
+            break;
+        }
+        let input = Input {
+            launch: tick == 0,
+            ..Input::default()
+        };
+        scene.update(&input, TICK);
+    }
+    assert!(
+        scene.get_current_level().count_remaining() < bricks,
+        "the ball didn't break a brick within {} ticks",
+        MAX_BREAK_TICKS
+    );
+    for _ in 0..6 {
+        scene.update(&Input::default(), TICK);
+    }
+    let image = render(&resources, DEFAULT_THEME, |renderer| scene.render(renderer));
+    check("breaking", &image);
+
+    // the pause overlay over the game, which doesn't move while it's up
+    let game = play(0, 60, |tick| Input {
+        launch: tick == 0,
+        pause: tick == 20,
+        ..Input::default()
+    });
+    check(
+        "paused",
+        &render(&resources, DEFAULT_THEME, |renderer| game.render(renderer)),
+    );
+}
+
+#[test]
+fn diff_marks_pixels_outside_the_tolerance() {
+    let expected = RgbaImage::from_pixel(3, 1, Rgba([100, 100, 100, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(1, 0, Rgba([104, 100, 96, 255]));
+    actual.put_pixel(2, 0, Rgba([100, 105, 100, 255]));
+
+    let (count, image) = diff(&actual, &expected, 4);
+    assert_eq!(count, 1);
+    assert_eq!(image.get_pixel(0, 0), &Rgba([25, 25, 25, 255]));
+    assert_eq!(image.get_pixel(1, 0), &Rgba([25, 25, 25, 255]));
+    assert_eq!(image.get_pixel(2, 0), &Rgba([255, 0, 0, 255]));
+
+    let (count, _) = diff(&RgbaImage::new(2, 2), &expected, 4);
+    assert_eq!(count, 4);
+}
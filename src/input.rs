@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use glium::glutin::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
+use glium::Display;
+use nalgebra::Vector2;
+
+use crate::viewport::Viewport;
+
+/// The player's controls for a single simulation tick.
+///
+/// This is everything the simulation reads from the outside world, so recording one of these per
+/// tick is enough to replay a game exactly.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Input {
+    #[serde(default)]
+    pub left: bool,
+    #[serde(default)]
+    pub right: bool,
+    #[serde(default)]
+    pub aim_left: bool,
+    #[serde(default)]
+    pub aim_right: bool,
+    #[serde(default)]
+    pub launch: bool,
+    #[serde(default)]
+    pub pointer: Option<f32>,
+    /// Set for the one tick after the pause key is pressed, to pause or unpause.
+    #[serde(default)]
+    pub pause: bool,
+}
+
+/// Collects window events into an `Input` for the next tick.
+#[derive(Default)]
+pub struct InputState {
+    keymap: HashMap<VirtualKeyCode, bool>,
+    pointer: Option<Vector2<f32>>,
+    follow_pointer: bool,
+    clicked: bool,
+    paused: bool,
+}
+
+impl InputState {
+    pub fn handle_event(&mut self, event: &Event, display: &Display) {
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return,
+        };
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                if let Some(code) = input.virtual_keycode {
+                    let pressed = input.state == ElementState::Pressed;
+                    if pressed && (code == VirtualKeyCode::Left || code == VirtualKeyCode::Right) {
+                        self.follow_pointer = false;
+                    }
+                    // held keys repeat, but holding pause shouldn't keep toggling it
+                    let pause = code == VirtualKeyCode::Escape || code == VirtualKeyCode::P;
+                    if pressed && pause && !self.is_key_pressed(code) {
+                        self.paused = true;
+                    }
+                    self.keymap.insert(code, pressed);
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let dpi_factor = display.gl_window().window().get_hidpi_factor();
+                let viewport = Viewport::fit(display.get_framebuffer_dimensions());
+                let position = position.to_physical(dpi_factor);
+                let position = viewport.to_game([position.x as f32, position.y as f32]);
+                self.pointer = Some(position);
+                self.follow_pointer = true;
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.clicked = true;
+            }
+            _ => (),
+        }
+    }
+
+    pub fn is_key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keymap.get(&key).cloned().unwrap_or(false)
+    }
+
+    /// Returns the cursor's position in game space, if it has been inside the window.
+    pub fn get_pointer(&self) -> Option<Vector2<f32>> {
+        self.pointer
+    }
+
+    /// Drops any clicks and pause presses that no tick has taken yet.
+    pub fn clear_pending(&mut self) {
+        self.clicked = false;
+        self.paused = false;
+    }
+
+    /// Returns the controls for the next tick, consuming any pending clicks and pause presses.
+    pub fn snapshot(&mut self) -> Input {
+        let clicked = self.clicked;
+        self.clicked = false;
+        let paused = self.paused;
+        self.paused = false;
+        Input {
+            left: self.is_key_pressed(VirtualKeyCode::Left),
+            right: self.is_key_pressed(VirtualKeyCode::Right),
+            aim_left: self.is_key_pressed(VirtualKeyCode::A),
+            aim_right: self.is_key_pressed(VirtualKeyCode::D),
+            launch: clicked || self.is_key_pressed(VirtualKeyCode::Space),
+            pointer: self
+                .pointer
+                .filter(|_| self.follow_pointer)
+                .map(|pointer| pointer[0]),
+            pause: paused,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_presses_can_be_dropped() {
+        let mut state = InputState {
+            clicked: true,
+            paused: true,
+            ..InputState::default()
+        };
+        state.clear_pending();
+        let input = state.snapshot();
+        assert!(!input.launch && !input.pause);
+    }
+}
@@ -1,104 +1,644 @@
-use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use glium::Texture2d;
-use nalgebra::{Vector2, Vector3};
+use nalgebra::Vector2;
 
-use crate::ball::Ball;
+use crate::animation::AnimationPlayer;
+use crate::collision::Collider;
+use crate::component::{Sprite, Transform};
+use crate::ecs::{Behaviour, EntityId, World};
 use crate::entity::Entity;
-use crate::math::calculate_vector_direction;
-use crate::resources::Resources;
-use crate::sprite::SpriteRenderer;
+use crate::renderer::{Layer, Renderer};
+use crate::tween::{Easing, Tween};
 use crate::{GAME_HEIGHT, GAME_WIDTH};
 
+/// The size of the cells that free-placed bricks are bucketed into for collision queries.
+const BUCKET_SIZE: f32 = 64.0;
+
+/// Identifies a brick in a level, either by its cell in the grid or by its index in the list of
+/// free-placed bricks. Ids order grid bricks first, row by row, then free bricks in file order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BrickId {
+    Grid(usize, usize),
+    Free(usize),
+}
+
+/// A level's bricks, stored as a dense grid of cells plus a list of bricks placed anywhere.
+///
+/// Free bricks are also indexed by the `BUCKET_SIZE` cells of the screen that they overlap, so
+/// looking up the bricks near a point doesn't depend on how many bricks there are.
+///
+/// The bricks' components are kept in the level's world, along with those of anything else put
+/// in play in the level.
 pub struct Level {
-    pub map: HashMap<(usize, usize), Brick>,
+    width: usize,
+    height: usize,
+    origin: Vector2<f32>,
+    pitch: Vector2<f32>,
+    grid: Vec<Option<Brick>>,
+    free: Vec<Brick>,
+    buckets: Vec<Vec<usize>>,
+    speed: BallSpeed,
+    theme: Option<String>,
+    world: World,
+}
+
+/// The animation destroyed bricks play as they break apart.
+const BREAK_ANIMATION: &str = "brick_break";
+
+/// How small destroyed bricks shrink to while they break, and how long it takes. A brick is
+/// gone once it has shrunk, so this is as long as the break animation.
+const BREAK_SCALE: f32 = 0.6;
+const BREAK_TIME: Duration = Duration::from_millis(180);
+
+/// The number of distinct brick codes, including 0 for an empty cell.
+pub const BRICK_CODES: u32 = 6;
+
+/// The code of bricks the ball can't break.
+const SOLID_BRICK: u32 = 1;
+
+/// The region of the screen that a level's brick grid is laid out in.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Area {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Area {
+    fn default() -> Self {
+        Area {
+            x: 0.0,
+            y: 0.0,
+            width: GAME_WIDTH as f32,
+            height: GAME_HEIGHT as f32 / 2.0,
+        }
+    }
+}
+
+/// How fast the ball moves in a level, in units per second.
+///
+/// The ball starts at `base` speed, which goes up by `ramp_per_second` for every second that it's
+/// in play and by `ramp_per_hits` every `hits` times it bounces off the paddle, always staying
+/// between `min` and `max`. The vertical part of its velocity is kept to at least `min_vertical`
+/// times its speed so it can't get stuck bouncing between the side walls.
+///
+/// Each bounce keeps `restitution` of the ball's speed into the surface it hit before the ball is
+/// brought back up to speed, so values below 1 flatten its bounces.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BallSpeed {
+    pub base: f32,
+    pub min: f32,
+    pub max: f32,
+    pub ramp_per_second: f32,
+    pub ramp_per_hits: f32,
+    pub hits: u32,
+    pub min_vertical: f32,
+    pub restitution: f32,
+}
+
+impl Default for BallSpeed {
+    fn default() -> Self {
+        BallSpeed {
+            base: 364.0,
+            min: 300.0,
+            max: 900.0,
+            ramp_per_second: 2.0,
+            ramp_per_hits: 25.0,
+            hits: 4,
+            min_vertical: 0.3,
+            restitution: 1.0,
+        }
+    }
+}
+
+/// A level as it's stored on disk.
+///
+/// The brick grid is `width` columns by `height` rows, and `map` lists the rows from top to
+/// bottom. The grid is laid out inside `area`, inset by `margin` on each side, with `gap` between
+/// neighbouring bricks.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LevelData {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub area: Area,
+    #[serde(default)]
+    pub gap: [f32; 2],
+    #[serde(default)]
+    pub margin: [f32; 2],
+    pub map: Vec<Vec<u32>>,
+    #[serde(default)]
+    pub bricks: Vec<FreeBrick>,
+    #[serde(default)]
+    pub speed: BallSpeed,
+    /// The name of the theme to draw the level with, instead of the one the player chose.
+    #[serde(default)]
+    pub theme: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct LevelData {
+/// A brick that isn't aligned to the level's grid.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FreeBrick {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub code: u32,
+}
+
+/// The original level format, which stored the number of columns in `rows` and the number of
+/// rows in `cols`, and always filled the top half of the screen.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LegacyLevelData {
     rows: u32,
     cols: u32,
     map: Vec<Vec<u32>>,
 }
 
+impl From<LegacyLevelData> for LevelData {
+    fn from(legacy: LegacyLevelData) -> Self {
+        LevelData {
+            width: legacy.rows,
+            height: legacy.cols,
+            map: legacy.map,
+            ..LevelData::default()
+        }
+    }
+}
+
+impl LevelData {
+    /// Parses and validates a level, converting it from the legacy format if needed.
+    pub fn from_json(data: impl AsRef<str>) -> serde_json::Result<Self> {
+        Ok(LevelData::from_json_with_format(data)?.0)
+    }
+
+    /// Like `from_json`, but also reports whether the data was in the legacy format.
+    fn from_json_with_format(data: impl AsRef<str>) -> serde_json::Result<(Self, bool)> {
+        use serde::de::Error;
+
+        let value = serde_json::from_str::<serde_json::Value>(data.as_ref())?;
+        let legacy = value.get("rows").is_some() && value.get("width").is_none();
+        let data: LevelData = if legacy {
+            serde_json::from_value::<LegacyLevelData>(value)?.into()
+        } else {
+            serde_json::from_value(value)?
+        };
+
+        if data.width == 0 || data.height == 0 {
+            return Err(serde_json::Error::custom("level must be at least 1x1"));
+        }
+        if data.map.len() > data.height as usize
+            || data.map.iter().any(|row| row.len() > data.width as usize)
+        {
+            return Err(serde_json::Error::custom(format!(
+                "map does not fit in a {}x{} grid",
+                data.width, data.height
+            )));
+        }
+        if let Some(code) = data.map.iter().flatten().find(|code| **code >= BRICK_CODES) {
+            return Err(serde_json::Error::custom(format!(
+                "unknown brick code {}",
+                code
+            )));
+        }
+        if !data.has_room_for_bricks() {
+            return Err(serde_json::Error::custom(
+                "gap and margin leave no room for bricks in the area",
+            ));
+        }
+        let speed = data.speed;
+        if speed.min <= 0.0
+            || speed.min > speed.max
+            || speed.min_vertical >= 1.0
+            || !(0.0..=1.0).contains(&speed.restitution)
+        {
+            return Err(serde_json::Error::custom(
+                "ball speed must have 0 < min <= max, min_vertical < 1 and 0 <= restitution <= 1",
+            ));
+        }
+        if let Some(brick) = data
+            .bricks
+            .iter()
+            .find(|brick| brick.code == 0 || brick.code >= BRICK_CODES)
+        {
+            return Err(serde_json::Error::custom(format!(
+                "unknown brick code {} for a free brick",
+                brick.code
+            )));
+        }
+        Ok((data, legacy))
+    }
+
+    /// Whether the bricks of the grid have some width and height once the gaps and margins are
+    /// taken out of the area.
+    pub fn has_room_for_bricks(&self) -> bool {
+        self.get_brick_size().iter().all(|side| *side > 0.0)
+    }
+
+    pub fn get_brick_size(&self) -> Vector2<f32> {
+        [
+            (self.area.width - 2.0 * self.margin[0] - (self.width - 1) as f32 * self.gap[0])
+                / self.width as f32,
+            (self.area.height - 2.0 * self.margin[1] - (self.height - 1) as f32 * self.gap[1])
+                / self.height as f32,
+        ]
+        .into()
+    }
+
+    /// The distance between the top-left corners of neighbouring bricks.
+    pub fn get_pitch(&self) -> Vector2<f32> {
+        self.get_brick_size() + Vector2::from(self.gap)
+    }
+
+    /// The top-left corner of the first brick in the grid.
+    pub fn get_origin(&self) -> Vector2<f32> {
+        [self.area.x + self.margin[0], self.area.y + self.margin[1]].into()
+    }
+
+    pub fn get_brick_position(&self, row: usize, column: usize) -> Vector2<f32> {
+        let pitch = self.get_pitch();
+        self.get_origin() + Vector2::new(pitch[0] * column as f32, pitch[1] * row as f32)
+    }
+
+    /// Serializes the level with one line per row of bricks, so the files stay readable.
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![
+            format!("\"width\": {}", self.width),
+            format!("\"height\": {}", self.height),
+        ];
+        if self.area != Area::default() {
+            fields.push(format!(
+                "\"area\": {}",
+                serde_json::to_string(&self.area).unwrap()
+            ));
+        }
+        if self.gap != [0.0, 0.0] {
+            fields.push(format!(
+                "\"gap\": {}",
+                serde_json::to_string(&self.gap).unwrap()
+            ));
+        }
+        if self.margin != [0.0, 0.0] {
+            fields.push(format!(
+                "\"margin\": {}",
+                serde_json::to_string(&self.margin).unwrap()
+            ));
+        }
+        if let Some(theme) = &self.theme {
+            fields.push(format!(
+                "\"theme\": {}",
+                serde_json::to_string(theme).unwrap()
+            ));
+        }
+        if self.speed != BallSpeed::default() {
+            fields.push(format!(
+                "\"speed\": {}",
+                serde_json::to_string(&self.speed).unwrap()
+            ));
+        }
+
+        let rows = self
+            .map
+            .iter()
+            .map(|row| serde_json::to_string(row).unwrap())
+            .collect::<Vec<_>>();
+        let mut json = format!(
+            "{{{}, \"map\": [\n    {}\n]",
+            fields.join(", "),
+            rows.join(",\n    ")
+        );
+        if !self.bricks.is_empty() {
+            let bricks = self
+                .bricks
+                .iter()
+                .map(|brick| serde_json::to_string(brick).unwrap())
+                .collect::<Vec<_>>();
+            json += &format!(", \"bricks\": [\n    {}\n]", bricks.join(",\n    "));
+        }
+        json + "}\n"
+    }
+}
+
+/// Returns `path` if it's a file, or every `.json` file in it in order of file name if it's a
+/// directory.
+pub fn list_files(path: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut paths = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.retain(|path| path.extension() == Some(OsStr::new("json")));
+    paths.sort();
+    Ok(paths)
+}
+
+/// Rewrites a level file in the legacy format to the current format.
+///
+/// Returns whether the file needed to be rewritten.
+pub fn migrate(path: impl AsRef<Path>) -> io::Result<bool> {
+    let path = path.as_ref();
+    let invalid = |err: serde_json::Error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), err),
+        )
+    };
+
+    let (data, legacy) =
+        LevelData::from_json_with_format(fs::read_to_string(path)?).map_err(invalid)?;
+    if legacy {
+        fs::write(path, data.to_json())?;
+    }
+    Ok(legacy)
+}
+
 impl Level {
     pub fn from_json(data: impl AsRef<str>) -> Self {
-        let data = serde_json::from_str::<LevelData>(data.as_ref()).unwrap();
-        let mut map = HashMap::new();
-
-        let unit_width = GAME_WIDTH as f32 / data.rows as f32;
-        let unit_height = GAME_HEIGHT as f32 / 2.0 / data.cols as f32;
-
-        for (i, row) in data.map.iter().enumerate() {
-            for (j, cell) in row.iter().enumerate() {
-                if *cell != 0 {
-                    let color = match *cell {
-                        1 => [0.8, 0.8, 0.7].into(),
-                        2 => [0.2, 0.6, 1.0].into(),
-                        3 => [0.0, 0.7, 0.0].into(),
-                        4 => [0.8, 0.8, 0.4].into(),
-                        5 => [1.0, 0.5, 0.0].into(),
-                        _ => unreachable!(),
-                    };
-                    let brick = Brick {
-                        position: [unit_width * j as f32, unit_height * i as f32].into(),
-                        size: [unit_width, unit_height].into(),
-                        destructible: *cell != 1,
-                        color,
-                        destroyed: false,
-                    };
-                    map.insert((i, j), brick);
-                }
+        Level::try_from_json(data).unwrap()
+    }
+
+    pub fn try_from_json(data: impl AsRef<str>) -> serde_json::Result<Self> {
+        Ok(Level::from_data(&LevelData::from_json(data)?))
+    }
+
+    pub fn from_data(data: &LevelData) -> Self {
+        let (width, height) = (data.width as usize, data.height as usize);
+        let size = data.get_brick_size();
+        let mut world = World::default();
+
+        let mut grid = Vec::with_capacity(width * height);
+        for i in 0..height {
+            for j in 0..width {
+                let code = data
+                    .map
+                    .get(i)
+                    .and_then(|row| row.get(j))
+                    .cloned()
+                    .unwrap_or(0);
+                let position = data.get_brick_position(i, j);
+                grid.push(Brick::new(&mut world, code, position, size));
+            }
+        }
+
+        let free = data
+            .bricks
+            .iter()
+            .filter_map(|brick| {
+                let (position, size) = ([brick.x, brick.y], [brick.width, brick.height]);
+                Brick::new(&mut world, brick.code, position, size)
+            })
+            .collect::<Vec<_>>();
+        let mut buckets = vec![Vec::new(); get_bucket_columns() * get_bucket_rows()];
+        for (index, brick) in free.iter().enumerate() {
+            let transform = brick.get_transform(&world);
+            let (min, max) = (transform.position, transform.get_max());
+            for bucket in get_bucket_range(min, max) {
+                buckets[bucket].push(index);
             }
         }
 
-        Level { map }
+        Level {
+            width,
+            height,
+            origin: data.get_origin(),
+            pitch: data.get_pitch(),
+            grid,
+            free,
+            buckets,
+            speed: data.speed,
+            theme: data.theme.clone(),
+            world,
+        }
+    }
+
+    pub fn get_ball_speed(&self) -> BallSpeed {
+        self.speed
+    }
+
+    pub fn get_theme(&self) -> Option<&str> {
+        self.theme.as_deref()
+    }
+
+    pub fn get_world(&self) -> &World {
+        &self.world
     }
 
-    pub fn render(&self, renderer: &mut SpriteRenderer) {
-        for ((x, y), brick) in self.map.iter() {
-            if !brick.is_destroyed() {
-                brick.render(renderer);
+    pub fn get_world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Loads a single level file, or every level file in a directory.
+    pub fn load_all(path: impl AsRef<Path>) -> io::Result<Vec<Self>> {
+        list_files(path)?
+            .iter()
+            .map(|path| {
+                let data = fs::read_to_string(path)?;
+                Level::try_from_json(data).map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: {}", path.display(), err),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    pub fn get_brick(&self, id: BrickId) -> Option<&Brick> {
+        match id {
+            BrickId::Grid(row, column) if row < self.height && column < self.width => {
+                self.grid[row * self.width + column].as_ref()
             }
+            BrickId::Grid(..) => None,
+            BrickId::Free(index) => self.free.get(index),
         }
     }
 
-    pub fn perform_collisions(&mut self, position: Vector2<f32>, radius: f32) -> CollisionResult {
-        for brick in self.map.values_mut() {
-            if brick.is_destructible() && !brick.is_destroyed() {
-                if let CollisionResult::Hit(direction, asdf) = brick.collides_with(position, radius)
-                {
-                    brick.destroy();
+    /// Returns the brick along with the world its components are in, so both can be changed.
+    fn get_brick_mut(&mut self, id: BrickId) -> Option<(&mut Brick, &mut World)> {
+        let brick = match id {
+            BrickId::Grid(row, column) if row < self.height && column < self.width => {
+                self.grid[row * self.width + column].as_mut()
+            }
+            BrickId::Grid(..) => None,
+            BrickId::Free(index) => self.free.get_mut(index),
+        }?;
+        Some((brick, &mut self.world))
+    }
+
+    pub fn destroy_brick(&mut self, id: BrickId) {
+        if let Some((brick, world)) = self.get_brick_mut(id) {
+            brick.destroy(world);
+        }
+    }
+
+    /// Iterates over every brick in the level in `BrickId` order.
+    pub fn bricks(&self) -> impl Iterator<Item = (BrickId, &Brick)> {
+        let width = self.width;
+        let grid = self
+            .grid
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, brick)| {
+                brick
+                    .as_ref()
+                    .map(|brick| (BrickId::Grid(index / width, index % width), brick))
+            });
+        let free = self
+            .free
+            .iter()
+            .enumerate()
+            .map(|(index, brick)| (BrickId::Free(index), brick));
+        grid.chain(free)
+    }
+
+    /// Finds the bricks whose cells or buckets overlap the box from `min` to `max`, in `BrickId`
+    /// order. The bricks themselves might not overlap the box.
+    pub fn query(&self, min: Vector2<f32>, max: Vector2<f32>) -> Vec<BrickId> {
+        let mut result = Vec::new();
+
+        let cell = |point: Vector2<f32>| {
+            let cell = (point - self.origin).component_div(&self.pitch);
+            (cell[1].floor(), cell[0].floor())
+        };
+        let (top, left) = cell(min);
+        let (bottom, right) = cell(max);
+        if bottom >= 0.0 && right >= 0.0 && top < self.height as f32 && left < self.width as f32 {
+            let (top, left) = (top.max(0.0) as usize, left.max(0.0) as usize);
+            let bottom = (bottom as usize).min(self.height - 1);
+            let right = (right as usize).min(self.width - 1);
+            for row in top..=bottom {
+                for column in left..=right {
+                    if self.grid[row * self.width + column].is_some() {
+                        result.push(BrickId::Grid(row, column));
+                    }
                 }
             }
         }
+
+        let start = result.len();
+        for bucket in get_bucket_range(min, max) {
+            result.extend(
+                self.buckets[bucket]
+                    .iter()
+                    .map(|index| BrickId::Free(*index)),
+            );
+        }
+        result[start..].sort();
+        result.dedup();
+        result
+    }
+
+    pub fn count_destructible(&self) -> usize {
+        self.bricks()
+            .filter(|(_, brick)| brick.is_destructible())
+            .count()
+    }
+
+    pub fn count_remaining(&self) -> usize {
+        self.bricks()
+            .filter(|(_, brick)| brick.is_destructible() && !brick.is_destroyed())
+            .count()
+    }
+
+    /// Runs the systems of the level's world for `delta`, which breaks destroyed bricks apart
+    /// and moves everything else in play.
+    pub fn update(&mut self, delta: Duration) {
+        self.world.update(delta);
+    }
+
+    /// Draws everything in the level's world.
+    pub fn render(&self, renderer: &mut dyn Renderer) {
+        self.world.render(renderer);
     }
 }
 
-pub struct Brick {
-    position: Vector2<f32>,
-    size: Vector2<f32>,
-    destructible: bool,
-    color: Vector3<f32>,
-    destroyed: bool,
+fn get_bucket_columns() -> usize {
+    (GAME_WIDTH as f32 / BUCKET_SIZE).ceil() as usize
+}
+
+fn get_bucket_rows() -> usize {
+    (GAME_HEIGHT as f32 / BUCKET_SIZE).ceil() as usize
+}
+
+/// Lists the buckets overlapping the box from `min` to `max`. Anything off the screen belongs to
+/// the nearest bucket on the edge.
+fn get_bucket_range(min: Vector2<f32>, max: Vector2<f32>) -> impl Iterator<Item = usize> {
+    let (columns, rows) = (get_bucket_columns(), get_bucket_rows());
+    let bucket =
+        |value: f32, count: usize| ((value / BUCKET_SIZE).floor().max(0.0) as usize).min(count - 1);
+    let (left, right) = (bucket(min[0], columns), bucket(max[0], columns));
+    let (top, bottom) = (bucket(min[1], rows), bucket(max[1], rows));
+    (top..=bottom).flat_map(move |row| (left..=right).map(move |column| row * columns + column))
 }
 
-#[derive(Copy, Clone)]
-pub enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
+/// Whether the ball can break bricks with this code.
+fn is_destructible(code: u32) -> bool {
+    code != SOLID_BRICK
 }
 
-pub enum CollisionResult {
-    Hit(Direction, Vector2<f32>),
-    Miss,
+/// The sprite that bricks with this code are drawn with, before their theme colour.
+pub fn get_brick_sprite(code: u32) -> &'static str {
+    if is_destructible(code) {
+        "block"
+    } else {
+        "block_solid"
+    }
+}
+
+pub struct Brick {
+    id: EntityId,
+    code: u32,
+    destroyed: bool,
 }
 
 impl Brick {
-    pub fn destroy(&mut self) {
+    /// Spawns a brick into `world`, unless `code` is for an empty cell.
+    fn new(
+        world: &mut World,
+        code: u32,
+        position: impl Into<Vector2<f32>>,
+        size: impl Into<Vector2<f32>>,
+    ) -> Option<Self> {
+        if code == 0 {
+            return None;
+        }
+        let id = world.spawn();
+        world.transforms.insert(id, Transform::new(position, size));
+        let sprite = Sprite::new(get_brick_sprite(code)).with_layer(Layer::Bricks);
+        world.sprites.insert(id, sprite.with_palette(code));
+        world.colliders.insert(id, Collider::Aabb);
+        Some(Brick {
+            id,
+            code,
+            destroyed: false,
+        })
+    }
+
+    /// Stops the brick colliding with anything and has it break apart. It's drawn breaking
+    /// apart while it shrinks, and not at all once it has.
+    pub fn destroy(&mut self, world: &mut World) {
+        if self.destroyed {
+            return;
+        }
         self.destroyed = true;
+        world.colliders.remove(self.id);
+        if let Some(sprite) = world.sprites.get_mut(self.id) {
+            sprite.animation = Some(AnimationPlayer::new(BREAK_ANIMATION));
+        }
+        let scale = Tween::new(1.0).to(BREAK_SCALE, BREAK_TIME, Easing::QuadIn);
+        world
+            .behaviours
+            .insert(self.id, Box::new(Breaking { scale }));
     }
 
     pub fn is_destroyed(&self) -> bool {
@@ -106,46 +646,247 @@ impl Brick {
     }
 
     pub fn is_destructible(&self) -> bool {
-        self.destructible
+        is_destructible(self.code)
     }
+}
 
-    pub fn collides_with(&self, position: Vector2<f32>, radius: f32) -> CollisionResult {
-        let center = position + radius * Vector2::identity();
-        let half_extents = self.get_size() / 2.0;
-        let brick_center = self.get_position() + half_extents;
+impl Entity for Brick {
+    fn get_id(&self) -> EntityId {
+        self.id
+    }
+}
 
-        let difference = center - brick_center;
-        let clamped = glm::clamp_vec(&difference, &-half_extents, &half_extents);
-        let closest = brick_center + clamped;
-        let difference = closest - center;
+/// Shrinks a destroyed brick while it breaks apart, and takes its sprite away once it has.
+struct Breaking {
+    scale: Tween<f32>,
+}
 
-        if glm::length(&difference) < radius {
-            let direction = calculate_vector_direction(&difference);
-            CollisionResult::Hit(direction, difference)
-        } else {
-            CollisionResult::Miss
+impl Behaviour for Breaking {
+    fn update(&mut self, id: EntityId, world: &mut World, delta: Duration) -> bool {
+        let finished = self.scale.update(delta);
+        if let Some(transform) = world.transforms.get_mut(id) {
+            transform.scale = Vector2::repeat(self.scale.get_value());
+        }
+        if finished {
+            world.sprites.remove(id);
         }
+        !finished
     }
 }
 
-impl Entity for Brick {
-    fn get_sprite(&self) -> &str {
-        if self.destructible {
-            "block"
-        } else {
-            "block_solid"
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: [&str; 4] = [
+        include_str!("../levels/level1.json"),
+        include_str!("../levels/level2.json"),
+        include_str!("../levels/level3.json"),
+        include_str!("../levels/level4.json"),
+    ];
+
+    fn assert_rect(level: &Level, cell: (usize, usize), position: [f32; 2], size: [f32; 2]) {
+        let brick = level.get_brick(BrickId::Grid(cell.0, cell.1)).unwrap();
+        let world = level.get_world();
+        let (actual_position, actual_size) = (brick.get_position(world), brick.get_size(world));
+        for i in 0..2 {
+            assert!(
+                (actual_position[i] - position[i]).abs() < 1e-3
+                    && (actual_size[i] - size[i]).abs() < 1e-3,
+                "brick {:?} is at {:?} with size {:?}, expected {:?} with size {:?}",
+                cell,
+                actual_position,
+                actual_size,
+                position,
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn shipped_level_grids() {
+        let grids = LEVELS
+            .iter()
+            .map(|level| {
+                let data = LevelData::from_json(level).unwrap();
+                (data.width, data.height)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(grids, vec![(15, 8), (15, 8), (13, 9), (13, 6)]);
+    }
+
+    #[test]
+    fn shipped_level_brick_rects() {
+        let level = Level::from_json(LEVELS[0]);
+        let width = GAME_WIDTH as f32 / 15.0;
+        assert_rect(&level, (0, 0), [0.0, 0.0], [width, 48.0]);
+        assert_rect(&level, (0, 14), [14.0 * width, 0.0], [width, 48.0]);
+        assert_rect(&level, (7, 0), [0.0, 336.0], [width, 48.0]);
+        assert_rect(&level, (7, 14), [14.0 * width, 336.0], [width, 48.0]);
+
+        let level = Level::from_json(LEVELS[2]);
+        let (width, height) = (GAME_WIDTH as f32 / 13.0, GAME_HEIGHT as f32 / 2.0 / 9.0);
+        assert_rect(&level, (8, 8), [8.0 * width, 8.0 * height], [width, height]);
+
+        let level = Level::from_json(LEVELS[3]);
+        let width = GAME_WIDTH as f32 / 13.0;
+        assert_rect(&level, (5, 6), [6.0 * width, 320.0], [width, 64.0]);
+    }
+
+    #[test]
+    fn shipped_levels_fill_top_half() {
+        for level in LEVELS.iter() {
+            let data = LevelData::from_json(level).unwrap();
+            let level = Level::from_data(&data);
+            let bricks = data.map.iter().flatten().filter(|code| **code != 0).count();
+            assert_eq!(level.bricks().count(), bricks);
+
+            for (_, brick) in level.bricks() {
+                let world = level.get_world();
+                let (position, size) = (brick.get_position(world), brick.get_size(world));
+                assert!(position[0] >= 0.0 && position[1] >= 0.0);
+                assert!(position[0] + size[0] <= GAME_WIDTH as f32 + 1e-3);
+                assert!(position[1] + size[1] <= GAME_HEIGHT as f32 / 2.0 + 1e-3);
+            }
         }
     }
 
-    fn get_position(&self) -> Vector2<f32> {
-        self.position
+    #[test]
+    fn legacy_format_is_migrated() {
+        let legacy = r#"{"rows": 3, "cols": 2, "map": [[1, 0, 2], [0, 3, 0]]}"#;
+        let (data, was_legacy) = LevelData::from_json_with_format(legacy).unwrap();
+        assert!(was_legacy);
+        assert_eq!((data.width, data.height), (3, 2));
+
+        let level = Level::from_data(&data);
+        let width = GAME_WIDTH as f32 / 3.0;
+        assert_rect(&level, (0, 2), [2.0 * width, 0.0], [width, 192.0]);
+        assert_rect(&level, (1, 1), [width, 192.0], [width, 192.0]);
+
+        let (migrated, was_legacy) = LevelData::from_json_with_format(data.to_json()).unwrap();
+        assert!(!was_legacy);
+        assert_eq!(migrated, data);
+    }
+
+    #[test]
+    fn area_gap_and_margin() {
+        let level = Level::from_json(
+            r#"{"width": 4, "height": 2,
+                "area": {"x": 100, "y": 50, "width": 440, "height": 130},
+                "gap": [10, 10], "margin": [20, 5],
+                "map": [[1, 1, 1, 1], [2, 2, 2, 2]]}"#,
+        );
+        assert_rect(&level, (0, 0), [120.0, 55.0], [92.5, 55.0]);
+        assert_rect(&level, (0, 3), [427.5, 55.0], [92.5, 55.0]);
+        assert_rect(&level, (1, 1), [222.5, 120.0], [92.5, 55.0]);
+    }
+
+    #[test]
+    fn theme_is_kept_when_saving() {
+        let data =
+            LevelData::from_json(r#"{"width": 1, "height": 1, "theme": "night", "map": [[1]]}"#)
+                .unwrap();
+        assert_eq!(Level::from_data(&data).get_theme(), Some("night"));
+        assert_eq!(LevelData::from_json(data.to_json()).unwrap(), data);
+    }
+
+    #[test]
+    fn invalid_levels_are_rejected() {
+        assert!(LevelData::from_json(r#"{"width": 2, "height": 1, "map": [[9]]}"#).is_err());
+        assert!(LevelData::from_json(r#"{"width": 1, "height": 1, "map": [[1, 1]]}"#).is_err());
+        assert!(LevelData::from_json(r#"{"width": 0, "height": 1, "map": []}"#).is_err());
+
+        // bricks of no width, of negative height, and of no height
+        let squeezed = |gap: &str, margin: &str| {
+            LevelData::from_json(format!(
+                r#"{{"width": 3, "height": 2,
+                    "area": {{"x": 0, "y": 0, "width": 100, "height": 50}},
+                    "gap": {}, "margin": {}, "map": []}}"#,
+                gap, margin
+            ))
+        };
+        assert!(squeezed("[10, 0]", "[40, 0]").is_err());
+        assert!(squeezed("[0, 60]", "[0, 0]").is_err());
+        assert!(squeezed("[0, 0]", "[0, 25]").is_err());
+        assert!(squeezed("[10, 10]", "[10, 10]").is_ok());
+
+        let speed = |speed: &str| {
+            LevelData::from_json(format!(
+                r#"{{"width": 1, "height": 1, "map": [], "speed": {}}}"#,
+                speed
+            ))
+        };
+        assert!(speed(r#"{"min": 500, "max": 400}"#).is_err());
+        assert!(speed(r#"{"min_vertical": 1}"#).is_err());
+        assert!(speed(r#"{"restitution": -0.5}"#).is_err());
+        assert!(speed(r#"{"restitution": 1.5}"#).is_err());
+        assert!(speed(r#"{"restitution": 0}"#).is_ok());
+    }
+
+    #[test]
+    fn query_finds_overlapping_cells_in_order() {
+        let level = Level::from_json(
+            r#"{"width": 4, "height": 2, "area": {"x": 0, "y": 0, "width": 400, "height": 100},
+                "map": [[1, 1, 0, 1], [2, 2, 2, 2]],
+                "bricks": [{"x": 150, "y": 60, "width": 10, "height": 10, "code": 3},
+                           {"x": 900, "y": 700, "width": 20, "height": 20, "code": 4}]}"#,
+        );
+
+        let ids = level.query([90.0, 40.0].into(), [210.0, 60.0].into());
+        assert_eq!(
+            ids,
+            vec![
+                BrickId::Grid(0, 0),
+                BrickId::Grid(0, 1),
+                BrickId::Grid(1, 0),
+                BrickId::Grid(1, 1),
+                BrickId::Grid(1, 2),
+                BrickId::Free(0),
+            ]
+        );
+
+        assert_eq!(
+            level.query([905.0, 705.0].into(), [910.0, 710.0].into()),
+            vec![BrickId::Free(1)]
+        );
+        assert!(level
+            .query([500.0, 300.0].into(), [520.0, 320.0].into())
+            .is_empty());
     }
 
-    fn get_size(&self) -> Vector2<f32> {
-        self.size
+    #[test]
+    fn bricks_are_in_id_order() {
+        let level = Level::from_json(LEVELS[0]);
+        let ids = level.bricks().map(|(id, _)| id).collect::<Vec<_>>();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
     }
 
-    fn get_color(&self) -> Vector3<f32> {
-        self.color
+    #[test]
+    fn destroyed_bricks_play_the_break_animation() {
+        let mut level = Level::from_json(LEVELS[0]);
+        let id = BrickId::Grid(0, 0);
+        let entity = level.get_brick(id).unwrap().get_id();
+        let sprite = |level: &Level| level.get_world().sprites.get(entity).cloned();
+        assert!(sprite(&level).unwrap().animation.is_none());
+
+        level.destroy_brick(id);
+        // the ball can't hit it any more
+        assert!(level.get_world().get_shape(entity).is_none());
+        level.update(Duration::from_millis(30));
+        let breaking = sprite(&level).unwrap().animation.unwrap();
+        assert_eq!(breaking.get_name(), BREAK_ANIMATION);
+
+        // hitting a broken brick again doesn't start it over
+        level.destroy_brick(id);
+        assert_eq!(sprite(&level).unwrap().animation, Some(breaking));
+
+        // the brick isn't drawn once it has shrunk
+        level.update(BREAK_TIME - Duration::from_millis(40));
+        assert!(sprite(&level).is_some());
+        level.update(Duration::from_millis(10));
+        assert!(sprite(&level).is_none());
+        assert!(level.get_world().behaviours.get(entity).is_none());
     }
 }
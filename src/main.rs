@@ -4,66 +4,416 @@ extern crate nalgebra_glm as glm;
 #[macro_use]
 extern crate serde_derive;
 
+mod animation;
+mod assets;
+mod atlas;
 mod ball;
+mod collision;
+mod component;
+mod ecs;
+mod editor;
 mod entity;
+mod font;
 mod game;
+#[cfg(test)]
+mod golden;
+mod input;
 mod level;
 mod math;
+mod options;
+mod pause;
+mod play;
 mod player;
+mod renderer;
+mod replay;
 mod resources;
+mod scene;
+mod software;
 mod sprite;
+mod theme;
+mod tween;
+mod viewport;
 
-use std::collections::HashMap;
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
 
-use crate::game::Game;
+use structopt::StructOpt;
+
+use crate::assets::{Manifest, Watcher};
+use crate::editor::Editor;
+use crate::game::{Game, TICK};
+use crate::input::{Input, InputState};
+use crate::level::Level;
+use crate::options::Options;
+use crate::renderer::Renderer;
+use crate::replay::Replay;
 use crate::resources::Resources;
+use crate::software::SoftwareRenderer;
+
+/// How often `--watch` checks for changed files.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
 const GAME_WIDTH: u32 = 1024;
 const GAME_HEIGHT: u32 = 768;
 
 fn main() {
+    let options = Options::from_args();
+
+    if !options.migrate_levels.is_empty() {
+        migrate_levels(&options.migrate_levels);
+        return;
+    }
+
+    let levels = match &options.levels {
+        Some(path) => match Level::load_all(path) {
+            Ok(levels) => levels,
+            Err(err) => fail(format!("failed to load levels: {}", err)),
+        },
+        None => game::default_levels(),
+    };
+    if levels.is_empty() {
+        fail("no levels to play");
+    }
+
+    let playback = options.play.as_ref().map(|path| match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(err) => fail(format!("failed to load replay {}: {}", path.display(), err)),
+    });
+    let (seed, level) = match &playback {
+        Some(replay) => (replay.seed, replay.level),
+        None => (options.seed.unwrap_or_else(rand::random), options.level - 1),
+    };
+    if level >= levels.len() {
+        fail(format!(
+            "level {} does not exist, there are {} levels",
+            level + 1,
+            levels.len()
+        ));
+    }
+
+    let mut game = match &options.edit {
+        Some(path) => match Editor::open(path) {
+            Ok(editor) => Game::with_editor(editor, seed),
+            Err(err) => fail(format!("failed to open {}: {}", path.display(), err)),
+        },
+        None => Game::new(levels, level, seed),
+    };
+    let mut recording = options.record.as_ref().map(|_| Replay::new(seed, level));
+    let mut inputs = playback.as_ref().map(|replay| replay.inputs());
+
+    if let Some(ticks) = options.headless {
+        for _ in 0..ticks {
+            let input = inputs
+                .as_mut()
+                .and_then(|inputs| inputs.next())
+                .unwrap_or_default();
+            if let Some(recording) = &mut recording {
+                recording.record(input);
+            }
+            game.update(&input, TICK);
+        }
+        println!("{}", game);
+        if let Some(path) = &options.screenshot {
+            save_screenshot(&options, &game, path);
+        }
+    } else {
+        run_window(&options, &mut game, |input_state| {
+            let input = match &mut inputs {
+                Some(inputs) => inputs.next().unwrap_or_default(),
+                None => input_state.snapshot(),
+            };
+            if let Some(recording) = &mut recording {
+                recording.record(input);
+            }
+            input
+        });
+    }
+
+    if let (Some(path), Some(recording)) = (&options.record, &recording) {
+        if let Err(err) = recording.save(path) {
+            fail(format!("failed to save replay {}: {}", path.display(), err));
+        }
+    }
+}
+
+fn migrate_levels(paths: &[PathBuf]) {
+    for path in paths {
+        let files = match level::list_files(path) {
+            Ok(files) => files,
+            Err(err) => fail(format!("failed to read {}: {}", path.display(), err)),
+        };
+        for file in files {
+            match level::migrate(&file) {
+                Ok(true) => println!("migrated {}", file.display()),
+                Ok(false) => println!("{} is up to date", file.display()),
+                Err(err) => fail(format!("failed to migrate {}: {}", file.display(), err)),
+            }
+        }
+    }
+}
+
+fn run_window(
+    options: &Options,
+    game: &mut Game,
+    mut next_input: impl FnMut(&mut InputState) -> Input,
+) {
     use glium::glutin::{
-        dpi::PhysicalSize, ContextBuilder, Event, EventsLoop, WindowBuilder, WindowEvent,
+        dpi::PhysicalSize, ContextBuilder, ElementState, Event, EventsLoop, KeyboardInput,
+        VirtualKeyCode, WindowBuilder, WindowEvent,
     };
-    use glium::{Display, Rect};
-    use nalgebra::Vector2;
+    use glium::Display;
+
+    use crate::sprite::SpriteRenderer;
 
     let mut events_loop = EventsLoop::new();
     let primary_monitor = events_loop.get_primary_monitor();
     let dpi_factor = primary_monitor.get_hidpi_factor();
-    let dimensions: PhysicalSize = (GAME_WIDTH, GAME_HEIGHT).into();
+    let dimensions: PhysicalSize = match options.resolution {
+        Some(resolution) => (resolution.width, resolution.height).into(),
+        None => (GAME_WIDTH, GAME_HEIGHT).into(),
+    };
 
+    let mut fullscreen = options.fullscreen && !options.windowed;
     let wb = WindowBuilder::new()
         .with_dimensions(dimensions.to_logical(dpi_factor))
-        .with_resizable(false)
+        .with_resizable(true)
+        .with_fullscreen(if fullscreen {
+            Some(primary_monitor)
+        } else {
+            None
+        })
         .with_title("Breakout");
-    let cb = ContextBuilder::new();
+    let cb = ContextBuilder::new().with_vsync(options.vsync);
     let display = Display::new(wb, cb, &events_loop).unwrap();
 
-    let mut game = Game::new(&display);
+    let mut manifest = load_manifest(options);
+    let mut resources = assets::load_resources(manifest.as_ref());
+    if let Err(err) = assets::upload_resources(&mut resources, &display, manifest.as_ref()) {
+        fail(format!("failed to load resources: {}", err));
+    }
+    if let Some(dir) = &options.dump_atlas {
+        match resources.get_atlas().save(dir) {
+            Ok(()) => eprintln!("saved texture atlas to {}", dir.display()),
+            Err(err) => eprintln!("failed to save texture atlas to {}: {}", dir.display(), err),
+        }
+    }
+    check_theme(options, &resources);
+    let mut theme = options.theme.clone();
+    let mut input_state = InputState::default();
+
+    let mut watcher = if options.watch {
+        Some(Watcher::new(get_watched_files(options, manifest.as_ref())))
+    } else {
+        None
+    };
+    let mut last_poll = Instant::now();
 
     let mut closed = false;
+    let mut lag = Duration::from_secs(0);
     let mut prev = Instant::now();
     while !closed {
         let now = Instant::now();
-        let delta = now - prev;
+        lag += now - prev;
 
         events_loop.poll_events(|event| match event {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
             } => closed = true,
-            _ => game.handle_event(event),
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::F11),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                fullscreen = !fullscreen;
+                let gl_window = display.gl_window();
+                let window = gl_window.window();
+                if fullscreen {
+                    window.set_fullscreen(Some(window.get_current_monitor()));
+                } else {
+                    window.set_fullscreen(None);
+                }
+            }
+            // there's no menu to pick a theme from yet, so T cycles through them
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::T),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let names = resources.get_theme_names();
+                let next = names
+                    .iter()
+                    .position(|name| *name == theme)
+                    .map_or(0, |i| i + 1);
+                theme = names[next % names.len()].to_owned();
+            }
+            _ => {
+                input_state.handle_event(&event, &display);
+                game.handle_event(&event, &mut input_state);
+            }
         });
 
-        game.update(delta);
+        while lag >= TICK {
+            let input = next_input(&mut input_state);
+            game.update(&input, TICK);
+            lag -= TICK;
+        }
+
+        if let Some(watcher) = &mut watcher {
+            if now - last_poll >= WATCH_INTERVAL {
+                last_poll = now;
+                let changed = watcher.poll();
+                if !changed.is_empty() {
+                    reload_changed(
+                        options,
+                        &display,
+                        &mut manifest,
+                        &mut resources,
+                        game,
+                        &changed,
+                    );
+                    *watcher = Watcher::new(get_watched_files(options, manifest.as_ref()));
+                }
+            }
+        }
 
         let mut target = display.draw();
-        let mut renderer = game.get_renderer(&mut target);
+        let mut renderer = SpriteRenderer::new(&display, &resources, &mut target)
+            .unwrap_or_else(|err| fail(err.to_string()));
+        renderer.begin_frame();
+        renderer.set_theme(&theme);
         game.render(&mut renderer);
+        renderer.end_frame();
         target.finish().unwrap();
 
         prev = now;
     }
 }
+
+fn load_manifest(options: &Options) -> Option<Manifest> {
+    options
+        .assets
+        .as_ref()
+        .map(|path| match Manifest::load(path) {
+            Ok(manifest) => manifest,
+            Err(err) => fail(format!("failed to load asset manifest: {}", err)),
+        })
+}
+
+fn check_theme(options: &Options, resources: &Resources) {
+    if resources.get_theme(&options.theme).is_none() {
+        fail(format!(
+            "no theme named '{}', expected one of: {}",
+            options.theme,
+            resources.get_theme_names().join(", ")
+        ));
+    }
+}
+
+/// Draws the game with the software renderer and saves the picture to `path`.
+fn save_screenshot(options: &Options, game: &Game, path: &Path) {
+    let manifest = load_manifest(options);
+    let resources = assets::load_resources(manifest.as_ref());
+    check_theme(options, &resources);
+
+    let mut renderer = SoftwareRenderer::new(&resources);
+    renderer.begin_frame();
+    renderer.set_theme(&options.theme);
+    game.render(&mut renderer);
+    renderer.end_frame();
+    if let Err(err) = renderer.get_image().save(path) {
+        fail(format!(
+            "failed to save screenshot {}: {}",
+            path.display(),
+            err
+        ));
+    }
+}
+
+/// Returns the files that `--watch` reloads when they change.
+fn get_watched_files(options: &Options, manifest: Option<&Manifest>) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let (Some(path), Some(manifest)) = (&options.assets, manifest) {
+        files.push(path.clone());
+        files.extend(manifest.files());
+    }
+    if let Some(path) = &options.levels {
+        // watching a directory itself notices levels being added or removed
+        files.push(path.clone());
+        files.extend(level::list_files(path).unwrap_or_default());
+    }
+    files
+}
+
+/// Reloads the assets and levels that were loaded from any of `changed`.
+fn reload_changed(
+    options: &Options,
+    display: &glium::Display,
+    manifest: &mut Option<Manifest>,
+    resources: &mut Resources,
+    game: &mut Game,
+    changed: &[PathBuf],
+) {
+    if let Some(path) = options
+        .assets
+        .as_ref()
+        .filter(|path| changed.contains(path))
+    {
+        match Manifest::load(path) {
+            Ok(reloaded) => {
+                let mut reloaded_resources = assets::load_resources(Some(&reloaded));
+                match assets::upload_resources(&mut reloaded_resources, display, Some(&reloaded)) {
+                    Ok(()) => {
+                        eprintln!("reloaded asset manifest {}", path.display());
+                        *resources = reloaded_resources;
+                        *manifest = Some(reloaded);
+                    }
+                    Err(err) => eprintln!("failed to reload resources: {}", err),
+                }
+            }
+            Err(err) => eprintln!("failed to reload asset manifest: {}", err),
+        }
+    } else if let Some(manifest) = manifest {
+        if let Err(err) = assets::reload_resources(resources, display, manifest, changed) {
+            eprintln!("failed to reload resources: {}", err);
+        }
+    }
+
+    if let Some(path) = &options.levels {
+        let files = level::list_files(path).unwrap_or_default();
+        if changed
+            .iter()
+            .any(|file| file == path || files.contains(file))
+        {
+            match Level::load_all(path) {
+                Ok(levels) => {
+                    eprintln!("reloaded levels from {}", path.display());
+                    game.reload_levels(levels);
+                }
+                Err(err) => eprintln!("failed to reload levels: {}", err),
+            }
+        }
+    }
+}
+
+fn fail(message: impl AsRef<str>) -> ! {
+    eprintln!("error: {}", message.as_ref());
+    process::exit(1);
+}
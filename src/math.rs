@@ -1,19 +1,69 @@
 use nalgebra::Vector2;
-use ordered_float::OrderedFloat;
 
-use crate::level::Direction;
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Circle {
+    pub center: Vector2<f32>,
+    pub radius: f32,
+}
+
+impl Circle {
+    /// The corners of the box containing the circle as it moves by `motion`.
+    pub fn get_swept_bounds(&self, motion: Vector2<f32>) -> (Vector2<f32>, Vector2<f32>) {
+        let end = self.center + motion;
+        let extent = Vector2::repeat(self.radius);
+        (
+            glm::min2(&self.center, &end) - extent,
+            glm::max2(&self.center, &end) + extent,
+        )
+    }
+}
+
+/// Reflects `velocity` off a surface facing `normal`, keeping `restitution` of the speed it had
+/// into the surface: 1 bounces it straight back off, and 0 leaves it sliding along the surface.
+///
+/// `normal` doesn't have to be a unit vector. Velocities heading away from the surface are left
+/// alone, as are all velocities when there's no normal to reflect off.
+pub fn reflect(velocity: Vector2<f32>, normal: Vector2<f32>, restitution: f32) -> Vector2<f32> {
+    let length = normal.norm();
+    if length == 0.0 || !length.is_finite() {
+        return velocity;
+    }
+    let normal = normal / length;
+    let into = velocity.dot(&normal);
+    if into >= 0.0 {
+        return velocity;
+    }
+    velocity - normal * (1.0 + restitution) * into
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflects_off_the_normal() {
+        let velocity = Vector2::new(3.0, 4.0);
+        assert_eq!(
+            reflect(velocity, Vector2::new(0.0, -2.0), 1.0),
+            Vector2::new(3.0, -4.0)
+        );
+        assert_eq!(
+            reflect(velocity, Vector2::new(0.0, -1.0), 0.0),
+            Vector2::new(3.0, 0.0)
+        );
+        // straight back off a surface facing it head on
+        let normal = -velocity.normalize();
+        assert!((reflect(velocity, normal, 1.0) + velocity).norm() < 1e-5);
+    }
 
-pub fn calculate_vector_direction(target: &Vector2<f32>) -> Direction {
-    let compass = [
-        (Direction::Up, Vector2::from([0.0, 1.0])),
-        (Direction::Right, Vector2::from([1.0, 0.0])),
-        (Direction::Down, Vector2::from([0.0, -1.0])),
-        (Direction::Left, Vector2::from([-1.0, 0.0])),
-    ];
-    let normal = target.normalize();
-    let (result, _) = compass
-        .into_iter()
-        .max_by_key(|(_, v)| OrderedFloat(normal.dot(v)))
-        .unwrap();
-    *result
+    #[test]
+    fn leaves_velocities_it_cannot_reflect_alone() {
+        let velocity = Vector2::new(3.0, 4.0);
+        assert_eq!(reflect(velocity, Vector2::new(0.0, 1.0), 1.0), velocity);
+        assert_eq!(reflect(velocity, Vector2::zeros(), 1.0), velocity);
+        assert_eq!(
+            reflect(velocity, Vector2::new(f32::NAN, 0.0), 1.0),
+            velocity
+        );
+    }
 }
@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "breakout")]
+pub struct Options {
+    /// Level to start on, counting from 1
+    #[structopt(
+        short = "l",
+        long = "level",
+        default_value = "1",
+        parse(try_from_str = "parse_level")
+    )]
+    pub level: usize,
+
+    /// Load levels from a JSON file, or every .json file in a directory
+    #[structopt(long = "levels", parse(from_os_str))]
+    pub levels: Option<PathBuf>,
+
+    /// Start in fullscreen mode
+    #[structopt(long = "fullscreen", conflicts_with = "windowed")]
+    pub fullscreen: bool,
+
+    /// Start in windowed mode (default)
+    #[structopt(long = "windowed")]
+    pub windowed: bool,
+
+    /// Window size, such as 1280x720
+    #[structopt(short = "r", long = "resolution")]
+    pub resolution: Option<Resolution>,
+
+    /// Visual theme to draw the game with, unless a level picks its own. Press T to switch
+    /// themes while playing
+    #[structopt(long = "theme", default_value = "classic")]
+    pub theme: String,
+
+    /// Synchronize buffer swaps with the display's refresh rate
+    #[structopt(long = "vsync")]
+    pub vsync: bool,
+
+    /// Seed for the game's random number generator
+    #[structopt(short = "s", long = "seed")]
+    pub seed: Option<u64>,
+
+    /// Play back a recorded replay; overrides --level and --seed
+    #[structopt(long = "play", parse(from_os_str), conflicts_with = "record")]
+    pub play: Option<PathBuf>,
+
+    /// Record the game's input to a replay file
+    #[structopt(long = "record", parse(from_os_str))]
+    pub record: Option<PathBuf>,
+
+    /// Open a level file in the level editor, creating it when saved if it doesn't exist. Editing
+    /// can't be recorded or played back, since replays only hold the game's input
+    #[structopt(
+        long = "edit",
+        parse(from_os_str),
+        conflicts_with = "record",
+        conflicts_with = "play"
+    )]
+    pub edit: Option<PathBuf>,
+
+    /// Rewrite level files or directories in the legacy format to the current format and exit
+    #[structopt(long = "migrate-levels", parse(from_os_str), value_name = "PATH")]
+    pub migrate_levels: Vec<PathBuf>,
+
+    /// Load textures and shaders from the files listed in an asset manifest, falling back to the
+    /// built-in ones
+    #[structopt(long = "assets", parse(from_os_str), value_name = "MANIFEST")]
+    pub assets: Option<PathBuf>,
+
+    /// Reload textures, shaders and levels from disk when they change. Levels can't change
+    /// while recording, or replays wouldn't match
+    #[structopt(long = "watch", conflicts_with = "record")]
+    pub watch: bool,
+
+    /// Write the packed texture atlas pages to PNG files in a directory on startup
+    #[structopt(long = "dump-atlas", parse(from_os_str), value_name = "DIR")]
+    pub dump_atlas: Option<PathBuf>,
+
+    /// Run without a window for the given number of ticks and print the final state
+    #[structopt(long = "headless", value_name = "TICKS", conflicts_with = "edit")]
+    pub headless: Option<u64>,
+
+    /// Save a picture of the end of a --headless run to a PNG file, drawn without a GPU
+    #[structopt(
+        long = "screenshot",
+        parse(from_os_str),
+        value_name = "FILE",
+        requires = "headless"
+    )]
+    pub screenshot: Option<PathBuf>,
+}
+
+/// Parses a level number, which counts from 1.
+fn parse_level(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("levels count from 1".to_owned()),
+        Ok(level) => Ok(level),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, 'x');
+        let mut next = || {
+            parts
+                .next()
+                .and_then(|part| part.trim().parse::<u32>().ok())
+                .filter(|value| *value > 0)
+                .ok_or_else(|| format!("invalid resolution '{}', expected WIDTHxHEIGHT", s))
+        };
+        let width = next()?;
+        let height = next()?;
+        Ok(Resolution { width, height })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<(u32, u32), String> {
+        s.parse::<Resolution>()
+            .map(|resolution| (resolution.width, resolution.height))
+    }
+
+    #[test]
+    fn parses_resolutions() {
+        assert_eq!(parse("1280x720"), Ok((1280, 720)));
+        assert_eq!(parse(" 800 x 600 "), Ok((800, 600)));
+        assert!(parse("1280").is_err());
+        assert!(parse("1280x").is_err());
+        assert!(parse("0x720").is_err());
+        assert!(parse("1280x0").is_err());
+        assert!(parse("widexhigh").is_err());
+        assert!(parse("1280x720x2").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn levels_count_from_one() {
+        let options = Options::from_iter_safe(&["breakout", "--level", "2"]).unwrap();
+        assert_eq!(options.level, 2);
+        assert!(Options::from_iter_safe(&["breakout", "--level", "0"]).is_err());
+        assert!(Options::from_iter_safe(&["breakout", "--level", "first"]).is_err());
+    }
+
+    #[test]
+    fn editing_is_not_recorded() {
+        let args = [
+            "breakout",
+            "--edit",
+            "level.json",
+            "--record",
+            "replay.json",
+        ];
+        assert!(Options::from_iter_safe(&args).is_err());
+        let args = ["breakout", "--edit", "level.json", "--play", "replay.json"];
+        assert!(Options::from_iter_safe(&args).is_err());
+        assert!(Options::from_iter_safe(&["breakout", "--edit", "level.json"]).is_ok());
+    }
+}
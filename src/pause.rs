@@ -0,0 +1,98 @@
+use std::fmt;
+use std::time::Duration;
+
+use nalgebra::Vector4;
+
+use crate::font;
+use crate::input::Input;
+use crate::renderer::{DrawParams, Layer, Renderer};
+use crate::scene::{Scene, Transition};
+use crate::{GAME_HEIGHT, GAME_WIDTH};
+
+const TEXT: &str = "PAUSED";
+const TEXT_HEIGHT: f32 = 42.0;
+
+/// How dark the game underneath is drawn, from 0 for untouched to 1 for black.
+const DIM: f32 = 0.6;
+
+/// Freezes the scene underneath, drawing it dimmed with a message over the top, until the pause
+/// key is pressed again.
+#[derive(Default)]
+pub struct PauseScene {
+    elapsed: Duration,
+}
+
+impl PauseScene {
+    pub fn new() -> Self {
+        PauseScene::default()
+    }
+}
+
+impl Scene for PauseScene {
+    fn get_name(&self) -> &str {
+        "pause"
+    }
+
+    fn update(&mut self, input: &Input, delta: Duration) -> Transition {
+        if input.pause {
+            return Transition::Pop;
+        }
+        self.elapsed += delta;
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        let params = DrawParams {
+            tint: Vector4::new(0.0, 0.0, 0.0, DIM),
+            ..DrawParams::new([0.0, 0.0], [GAME_WIDTH as f32, GAME_HEIGHT as f32])
+                .with_layer(Layer::Effects)
+        };
+        renderer.draw_sprite("blank", &params);
+
+        let width = font::get_advance(TEXT_HEIGHT) * TEXT.len() as f32;
+        let position = [
+            (GAME_WIDTH as f32 - width) / 2.0,
+            (GAME_HEIGHT as f32 - TEXT_HEIGHT) / 2.0,
+        ];
+        renderer.render_text(TEXT, position, TEXT_HEIGHT, [1.0, 1.0, 1.0]);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for PauseScene {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "paused: {:.2}s",
+            self.elapsed.as_millis() as f32 / 1000.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::TICK;
+
+    #[test]
+    fn pause_key_unpauses() {
+        let mut scene = PauseScene::new();
+        for _ in 0..3 {
+            let input = Input {
+                launch: true,
+                ..Input::default()
+            };
+            assert!(matches!(scene.update(&input, TICK), Transition::None));
+        }
+        assert_eq!(scene.to_string(), "paused: 0.03s");
+
+        let input = Input {
+            pause: true,
+            ..Input::default()
+        };
+        assert!(matches!(scene.update(&input, TICK), Transition::Pop));
+    }
+}
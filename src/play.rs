@@ -0,0 +1,231 @@
+use std::fmt;
+use std::time::Duration;
+
+use glium::glutin::{ElementState, Event, VirtualKeyCode, WindowEvent};
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+use crate::ball::Ball;
+use crate::entity::Entity;
+use crate::input::{Input, InputState};
+use crate::level::Level;
+use crate::pause::PauseScene;
+use crate::player::Player;
+use crate::renderer::Renderer;
+use crate::scene::{Scene, Transition};
+use crate::GAME_HEIGHT;
+
+/// Playing through a set of levels.
+pub struct PlayScene {
+    levels: Vec<Level>,
+    level: usize,
+    /// The only source of randomness in the simulation, seeded so replays repeat.
+    rng: Pcg32,
+    /// Whether the editor is test-playing its level, in which case F5, the key that started test
+    /// play, goes back to it. Escape still pauses, the same as in any other game.
+    testing: bool,
+
+    player: Player,
+    ball: Ball,
+}
+
+impl PlayScene {
+    pub fn new(mut levels: Vec<Level>, level: usize, seed: u64) -> Self {
+        assert!(level < levels.len(), "level {} does not exist", level);
+
+        let speed = levels[level].get_ball_speed();
+        let world = levels[level].get_world_mut();
+        let player = Player::new(world);
+        let ball = Ball::new(world, &player, speed);
+
+        PlayScene {
+            levels,
+            level,
+            rng: Pcg32::seed_from_u64(seed),
+            testing: false,
+
+            player,
+            ball,
+        }
+    }
+
+    /// Plays a level from the editor, going back to the editor on F5.
+    pub fn test_play(level: Level, seed: u64) -> Self {
+        PlayScene {
+            testing: true,
+            ..PlayScene::new(vec![level], 0, seed)
+        }
+    }
+
+    /// Puts a new paddle and ball in the current level. The old ones went with the world of the
+    /// level they were in.
+    fn reset(&mut self) {
+        let level = &mut self.levels[self.level];
+        let speed = level.get_ball_speed();
+        self.player = Player::new(level.get_world_mut());
+        self.ball = Ball::new(level.get_world_mut(), &self.player, speed);
+    }
+
+    pub fn get_current_level(&self) -> &Level {
+        &self.levels[self.level]
+    }
+}
+
+impl Scene for PlayScene {
+    fn get_name(&self) -> &str {
+        "play"
+    }
+
+    fn handle_input(&mut self, event: &Event, _input: &InputState) -> Transition {
+        if let Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { input, .. },
+            ..
+        } = event
+        {
+            if self.testing
+                && input.state == ElementState::Pressed
+                && input.virtual_keycode == Some(VirtualKeyCode::F5)
+            {
+                return Transition::Pop;
+            }
+        }
+        Transition::None
+    }
+
+    fn update(&mut self, input: &Input, delta: Duration) -> Transition {
+        if input.pause {
+            return Transition::Push(Box::new(PauseScene::new()));
+        }
+
+        let level = &mut self.levels[self.level];
+        let world = level.get_world_mut();
+        let start = self.player.get_position(world)[0];
+        if input.left {
+            self.player.move_left(world, delta);
+        } else if input.right {
+            self.player.move_right(world, delta);
+        } else if let Some(target) = input.pointer {
+            self.player.move_towards(world, target, delta);
+        } else {
+            self.player.coast(world, delta);
+        }
+        // moves the paddle, runs its squash and the breaking bricks, and plays every animation
+        level.update(delta);
+        let world = level.get_world_mut();
+        self.player.stop_at_walls(world);
+        // a stuck ball moves exactly as far as the paddle did, walls included
+        let moved = self.player.get_position(world)[0] - start;
+        self.ball.move_by(world, moved);
+
+        if input.aim_left {
+            self.ball.aim_left(delta);
+        } else if input.aim_right {
+            self.ball.aim_right(delta);
+        }
+        if input.launch {
+            self.ball.unstick(world, &mut self.rng);
+        }
+        let hits = self.ball.get_paddle_hits();
+        self.ball.update_position(delta, level, &self.player);
+        let speed = level.get_ball_speed();
+        let world = level.get_world_mut();
+        if self.ball.get_paddle_hits() > hits {
+            self.player.squash(world);
+        }
+
+        // the ball fell off the bottom of the screen
+        let ball = self.ball.get_circle(world);
+        if ball.center[1] - ball.radius > GAME_HEIGHT as f32 {
+            world.despawn(self.ball.get_id());
+            self.ball = Ball::new(world, &self.player, speed);
+        }
+        Transition::None
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        let level = self.get_current_level();
+        if let Some(theme) = level.get_theme() {
+            renderer.set_theme(theme);
+        }
+        renderer.render_background();
+        level.render(renderer);
+        self.ball.render_aim(level.get_world(), renderer);
+    }
+
+    /// Restarts the current level with the new levels. The editor's test play has its own copy
+    /// of the level being edited, so it keeps that.
+    fn reload_levels(&mut self, levels: Vec<Level>) {
+        if self.testing || levels.is_empty() {
+            return;
+        }
+        self.level = self.level.min(levels.len() - 1);
+        self.levels = levels;
+        self.reset();
+    }
+}
+
+impl fmt::Display for PlayScene {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = self.get_current_level();
+        let world = level.get_world();
+        let player = self.player.get_position(world);
+        let ball = self.ball.get_circle(world).center;
+        let velocity = self.ball.get_velocity(world);
+        writeln!(f, "level: {}/{}", self.level + 1, self.levels.len())?;
+        writeln!(f, "player: {:.2} {:.2}", player[0], player[1])?;
+        writeln!(
+            f,
+            "ball: {:.2} {:.2} velocity {:.2} {:.2} speed {:.2}{}",
+            ball[0],
+            ball[1],
+            velocity[0],
+            velocity[1],
+            self.ball.get_speed(),
+            if self.ball.is_stuck() { " (stuck)" } else { "" }
+        )?;
+        write!(
+            f,
+            "bricks: {}/{} remaining",
+            level.count_remaining(),
+            level.count_destructible()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{self, TICK};
+
+    #[test]
+    fn pausing_pushes_the_pause_overlay() {
+        let mut scene = PlayScene::new(game::default_levels(), 0, 1);
+        let input = Input {
+            pause: true,
+            left: true,
+            ..Input::default()
+        };
+        let position = |scene: &PlayScene| {
+            let world = scene.get_current_level().get_world();
+            scene.player.get_position(world)
+        };
+        let before = position(&scene);
+        match scene.update(&input, TICK) {
+            Transition::Push(pause) => assert_eq!(pause.get_name(), "pause"),
+            _ => panic!("pausing didn't push the pause overlay"),
+        }
+        // nothing else happens on the tick the game is paused
+        assert_eq!(position(&scene), before);
+    }
+
+    #[test]
+    fn test_play_keeps_its_level() {
+        let mut scene = PlayScene::test_play(game::default_levels().remove(0), 1);
+        scene.reload_levels(game::default_levels());
+        assert_eq!(scene.levels.len(), 1);
+
+        let mut scene = PlayScene::new(game::default_levels(), 3, 1);
+        scene.reload_levels(game::default_levels().into_iter().take(2).collect());
+        assert_eq!((scene.level, scene.levels.len()), (1, 2));
+    }
+}
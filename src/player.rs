@@ -2,60 +2,249 @@ use std::time::Duration;
 
 use nalgebra::Vector2;
 
+use crate::animation::AnimationPlayer;
+use crate::collision::Collider;
+use crate::component::{Sprite, Transform, Velocity};
+use crate::ecs::{Behaviour, EntityId, World};
 use crate::entity::Entity;
-use crate::sprite::SpriteRenderer;
+use crate::tween::{Easing, Tween};
 use crate::{GAME_HEIGHT, GAME_WIDTH};
 
+/// The fastest the paddle can move, in pixels per second.
+const MAX_SPEED: f32 = 600.0;
+
+/// How quickly the paddle speeds up while it's being steered, in pixels per second squared.
+const ACCELERATION: f32 = 5000.0;
+
+/// How quickly the paddle slows down once it's let go, in pixels per second squared.
+const FRICTION: f32 = 4000.0;
+
+/// How the paddle is squashed when the ball hits it, as a scale of its width and height, and how
+/// long it takes to squash and to spring back.
+const SQUASH: [f32; 2] = [1.12, 0.7];
+const SQUASH_TIME: Duration = Duration::from_millis(50);
+const SPRING_TIME: Duration = Duration::from_millis(300);
+
 pub struct Player {
-    position: Vector2<f32>,
-    size: Vector2<f32>,
-    velocity: f32,
+    id: EntityId,
 }
 
 impl Player {
-    pub fn new() -> Self {
+    /// Spawns the paddle in the middle of the bottom of the screen.
+    pub fn new(world: &mut World) -> Self {
         let size: Vector2<f32> = [100.0, 20.0].into();
         let position = [
             (GAME_WIDTH as f32 - size[0]) / 2.0,
             GAME_HEIGHT as f32 - size[1],
-        ]
-        .into();
-        Player {
-            position,
-            size,
-            velocity: 500.0,
-        }
+        ];
+        let id = world.spawn();
+        world.transforms.insert(id, Transform::new(position, size));
+        world.sprites.insert(
+            id,
+            Sprite::new("paddle").with_animation(AnimationPlayer::new("paddle")),
+        );
+        world.colliders.insert(id, Collider::Aabb);
+        // only ever horizontal
+        world.velocities.insert(id, Velocity::zeros());
+        Player { id }
     }
 
-    pub fn move_left(&mut self, delta: Duration) -> f32 {
-        let velocity = self.velocity * delta.as_millis() as f32 / 1000.0;
-        if self.position[0] > 0.0 {
-            self.position[0] -= velocity;
-            return -velocity;
+    /// Returns the paddle's horizontal velocity in pixels per second.
+    pub fn get_velocity(&self, world: &World) -> f32 {
+        world.velocities.get(self.id).unwrap()[0]
+    }
+
+    /// Squashes the paddle flat and lets it spring back, starting from however squashed it
+    /// already is.
+    pub fn squash(&self, world: &mut World) {
+        let scale = self.get_transform(world).scale;
+        let scale = Tween::new(scale)
+            .to(SQUASH.into(), SQUASH_TIME, Easing::QuadOut)
+            .to(Vector2::repeat(1.0), SPRING_TIME, Easing::ElasticOut);
+        world.behaviours.insert(self.id, Box::new(Squash { scale }));
+    }
+
+    // Each of the steering methods below sets the paddle's velocity for the next `delta`. The
+    // world's movement system then moves it, and `stop_at_walls` stops it at the walls.
+
+    pub fn move_left(&self, world: &mut World, delta: Duration) {
+        self.accelerate(world, -MAX_SPEED, ACCELERATION, delta);
+    }
+
+    pub fn move_right(&self, world: &mut World, delta: Duration) {
+        self.accelerate(world, MAX_SPEED, ACCELERATION, delta);
+    }
+
+    /// Steers the paddle's center towards `x`, slowing down in time to stop on it.
+    pub fn move_towards(&self, world: &mut World, x: f32, delta: Duration) {
+        let seconds = delta.as_millis() as f32 / 1000.0;
+        let (position, size) = (self.get_position(world), self.get_size(world));
+        let center = position[0] + size[0] / 2.0;
+        let target = x.clamp(size[0] / 2.0, GAME_WIDTH as f32 - size[0] / 2.0);
+        let distance = target - center;
+
+        // the fastest the paddle can go while still being able to stop at the target, and
+        // without passing it during this step
+        let speed = (2.0 * ACCELERATION * distance.abs())
+            .sqrt()
+            .min(distance.abs() / seconds)
+            .min(MAX_SPEED);
+        let velocity = speed.copysign(distance);
+        let current = self.get_velocity(world);
+        if velocity * current >= 0.0 && velocity.abs() < current.abs() {
+            world.velocities.get_mut(self.id).unwrap()[0] = velocity;
+        } else {
+            self.accelerate(world, velocity, ACCELERATION, delta);
         }
-        0.0
     }
 
-    pub fn move_right(&mut self, delta: Duration) -> f32 {
-        let velocity = self.velocity * delta.as_millis() as f32 / 1000.0;
-        if self.position[0] < (GAME_WIDTH as f32 - self.size[0]) {
-            self.position[0] += velocity;
-            return velocity;
+    /// Lets the paddle slide to a stop when it isn't being steered.
+    pub fn coast(&self, world: &mut World, delta: Duration) {
+        self.accelerate(world, 0.0, FRICTION, delta);
+    }
+
+    fn accelerate(&self, world: &mut World, target: f32, rate: f32, delta: Duration) {
+        let change = rate * delta.as_millis() as f32 / 1000.0;
+        let velocity = world.velocities.get_mut(self.id).unwrap();
+        velocity[0] += (target - velocity[0]).clamp(-change, change);
+    }
+
+    /// Puts the paddle back between the walls if it has moved past one, stopping it there.
+    pub fn stop_at_walls(&self, world: &mut World) {
+        let transform = world.transforms.get_mut(self.id).unwrap();
+        let limit = GAME_WIDTH as f32 - transform.size[0];
+        let x = transform.position[0];
+        transform.position[0] = x.clamp(0.0, limit);
+        if transform.position[0] != x {
+            world.velocities.get_mut(self.id).unwrap()[0] = 0.0;
         }
-        0.0
     }
 }
 
 impl Entity for Player {
-    fn get_sprite(&self) -> &str {
-        "paddle"
+    fn get_id(&self) -> EntityId {
+        self.id
     }
+}
 
-    fn get_position(&self) -> Vector2<f32> {
-        self.position
+/// Drives the paddle's scale while it's squashed and springs back.
+struct Squash {
+    scale: Tween<Vector2<f32>>,
+}
+
+impl Behaviour for Squash {
+    fn update(&mut self, id: EntityId, world: &mut World, delta: Duration) -> bool {
+        let finished = self.scale.update(delta);
+        if let Some(transform) = world.transforms.get_mut(id) {
+            transform.scale = self.scale.get_value();
+        }
+        !finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    /// Steers the paddle with `steer` for `ticks` ticks, moving it the way the game does, and
+    /// returns how far it moved.
+    fn run(
+        player: &Player,
+        world: &mut World,
+        ticks: usize,
+        mut steer: impl FnMut(&Player, &mut World),
+    ) -> f32 {
+        let start = player.get_position(world)[0];
+        for _ in 0..ticks {
+            steer(player, world);
+            world.update(STEP);
+            player.stop_at_walls(world);
+        }
+        player.get_position(world)[0] - start
+    }
+
+    #[test]
+    fn accelerates_up_to_max_speed() {
+        let mut world = World::default();
+        let player = Player::new(&mut world);
+        player.move_right(&mut world, STEP);
+        assert_eq!(player.get_velocity(&world), ACCELERATION * 0.01);
+
+        run(&player, &mut world, 20, |player, world| {
+            player.move_right(world, STEP)
+        });
+        assert_eq!(player.get_velocity(&world), MAX_SPEED);
+    }
+
+    #[test]
+    fn friction_stops_the_paddle() {
+        let mut world = World::default();
+        let player = Player::new(&mut world);
+        run(&player, &mut world, 20, |player, world| {
+            player.move_left(world, STEP)
+        });
+        let moved = run(&player, &mut world, 20, |player, world| {
+            player.coast(world, STEP)
+        });
+        assert_eq!(player.get_velocity(&world), 0.0);
+        assert!(moved < 0.0);
+    }
+
+    #[test]
+    fn stops_exactly_at_the_walls() {
+        let mut world = World::default();
+        let player = Player::new(&mut world);
+        let start = player.get_position(&world)[0];
+        let moved = run(&player, &mut world, 200, |player, world| {
+            player.move_left(world, STEP)
+        });
+        assert_eq!(player.get_position(&world)[0], 0.0);
+        assert_eq!(moved, -start);
+        assert_eq!(player.get_velocity(&world), 0.0);
+
+        let moved = run(&player, &mut world, 300, |player, world| {
+            player.move_right(world, STEP)
+        });
+        let limit = GAME_WIDTH as f32 - player.get_size(&world)[0];
+        assert_eq!(player.get_position(&world)[0], limit);
+        assert_eq!(moved, limit);
+        assert_eq!(player.get_velocity(&world), 0.0);
     }
 
-    fn get_size(&self) -> Vector2<f32> {
-        self.size
+    #[test]
+    fn pointer_stops_on_target() {
+        let mut world = World::default();
+        let player = Player::new(&mut world);
+        let target = 200.0;
+        let center =
+            |world: &World| player.get_position(world)[0] + player.get_size(world)[0] / 2.0;
+        for _ in 0..200 {
+            run(&player, &mut world, 1, |player, world| {
+                player.move_towards(world, target, STEP)
+            });
+            assert!(
+                center(&world) >= target - 1e-3,
+                "the paddle passed its target"
+            );
+        }
+        assert!((center(&world) - target).abs() < 1e-3);
+        assert_eq!(player.get_velocity(&world), 0.0);
+    }
+
+    #[test]
+    fn squash_springs_back() {
+        let mut world = World::default();
+        let player = Player::new(&mut world);
+        player.squash(&mut world);
+        run(&player, &mut world, 5, |_, _| ());
+        assert_eq!(player.get_transform(&world).scale, Vector2::from(SQUASH));
+        // the collision box doesn't change
+        assert_eq!(player.get_size(&world), Vector2::new(100.0, 20.0));
+
+        run(&player, &mut world, 30, |_, _| ());
+        assert_eq!(player.get_transform(&world).scale, Vector2::repeat(1.0));
+        assert!(world.behaviours.get(player.get_id()).is_none());
     }
 }
@@ -0,0 +1,375 @@
+use nalgebra::{Rotation2, Vector2, Vector3, Vector4};
+
+use crate::animation::AnimationPlayer;
+use crate::atlas::Region;
+use crate::font::{self, FONT, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::resources::Resources;
+use crate::theme::Theme;
+use crate::{GAME_HEIGHT, GAME_WIDTH};
+
+/// The layers that sprites are drawn on, from the back to the front. Everything on a layer is
+/// drawn over everything on the layers before it, whatever order it was drawn in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    Background,
+    Bricks,
+    // nothing gives off particles yet
+    #[allow(dead_code)]
+    Particles,
+    Entities,
+    Effects,
+    Ui,
+}
+
+/// Where and how a sprite is drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawParams {
+    /// The top-left corner of the sprite before it's rotated.
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    /// How far the sprite is turned around `origin`, in radians. Positive angles turn clockwise
+    /// on screen.
+    pub rotation: f32,
+    /// The point that the sprite turns around, as a fraction of its size from its top-left
+    /// corner, so `[0.5, 0.5]` is the centre.
+    pub origin: Vector2<f32>,
+    /// Mirrors the image left to right.
+    pub flip_x: bool,
+    /// Mirrors the image top to bottom.
+    pub flip_y: bool,
+    /// The part of the image to draw, as fractions of its size from its top-left corner. The
+    /// whole image is `[0.0, 0.0]` to `[1.0, 1.0]`.
+    pub source: (Vector2<f32>, Vector2<f32>),
+    /// Multiplies the colour of the image, with the last component multiplying its alpha.
+    pub tint: Vector4<f32>,
+    pub layer: Layer,
+}
+
+impl DrawParams {
+    /// Draws the whole image stretched over the rectangle at `position` and `size`, unturned and
+    /// untinted, on the entities layer.
+    pub fn new(position: impl Into<Vector2<f32>>, size: impl Into<Vector2<f32>>) -> Self {
+        DrawParams {
+            position: position.into(),
+            size: size.into(),
+            rotation: 0.0,
+            origin: Vector2::repeat(0.5),
+            flip_x: false,
+            flip_y: false,
+            source: (Vector2::zeros(), Vector2::repeat(1.0)),
+            tint: Vector4::repeat(1.0),
+            layer: Layer::Entities,
+        }
+    }
+
+    /// Sets the tint to `color`, fully opaque.
+    pub fn with_color(self, color: impl Into<Vector3<f32>>) -> Self {
+        let color = color.into();
+        DrawParams {
+            tint: Vector4::new(color[0], color[1], color[2], 1.0),
+            ..self
+        }
+    }
+
+    pub fn with_layer(self, layer: Layer) -> Self {
+        DrawParams { layer, ..self }
+    }
+
+    /// Maps a point on the sprite, as a fraction of its size from its top-left corner, to where
+    /// it's drawn in game space.
+    pub fn to_game(self, point: Vector2<f32>) -> Vector2<f32> {
+        let pivot = self.origin.component_mul(&self.size);
+        let offset = point.component_mul(&self.size) - pivot;
+        self.position + pivot + Rotation2::new(self.rotation) * offset
+    }
+
+    /// Returns the corners of the sprite in game space: top-left, top-right, bottom-right and
+    /// bottom-left.
+    pub fn get_corners(self) -> [Vector2<f32>; 4] {
+        [
+            self.to_game(Vector2::new(0.0, 0.0)),
+            self.to_game(Vector2::new(1.0, 0.0)),
+            self.to_game(Vector2::new(1.0, 1.0)),
+            self.to_game(Vector2::new(0.0, 1.0)),
+        ]
+    }
+
+    /// Returns the texture coordinates in `region` that a point on the sprite, as a fraction of
+    /// its size, is drawn from.
+    pub fn get_tex_coords(self, region: Region, point: Vector2<f32>) -> [f32; 2] {
+        let x = if self.flip_x {
+            1.0 - point[0]
+        } else {
+            point[0]
+        };
+        let y = if self.flip_y {
+            1.0 - point[1]
+        } else {
+            point[1]
+        };
+        let (min, max) = self.source;
+        let (x, y) = (
+            min[0] + x * (max[0] - min[0]),
+            min[1] + y * (max[1] - min[1]),
+        );
+        [
+            region.min[0] + x * (region.max[0] - region.min[0]),
+            region.min[1] + y * (region.max[1] - region.min[1]),
+        ]
+    }
+}
+
+/// Draw calls collected over a frame, so that they can be put in order before they're drawn.
+///
+/// Each call carries a `T` along with it for whatever else the backend needs to draw it with, like
+/// the shader that was in use when it was queued.
+pub struct DrawQueue<T = ()> {
+    calls: Vec<(Region, DrawParams, T)>,
+}
+
+impl<T> Default for DrawQueue<T> {
+    fn default() -> Self {
+        DrawQueue { calls: Vec::new() }
+    }
+}
+
+impl<T> DrawQueue<T> {
+    pub fn push(&mut self, region: Region, params: &DrawParams, extra: T) {
+        self.calls.push((region, *params, extra));
+    }
+
+    pub fn clear(&mut self) {
+        self.calls.clear();
+    }
+
+    /// Removes every call from the queue, in the order they should be drawn: by layer, then by
+    /// atlas page so that sprites sharing a page can be drawn together, and otherwise in the
+    /// order they were queued.
+    pub fn drain(&mut self) -> Vec<(Region, DrawParams, T)> {
+        let mut calls = std::mem::take(&mut self.calls);
+        calls.sort_by_key(|(region, params, _)| (params.layer, region.page));
+        calls
+    }
+}
+
+/// Something the game can be drawn with. Everything is drawn in game space, `GAME_WIDTH` by
+/// `GAME_HEIGHT` units with the origin at the top-left, from the texture atlas in `Resources`.
+///
+/// Backends only have to draw regions of the atlas; sprites and text are built on top of that.
+/// Nothing is guaranteed to be drawn until the end of the frame, or until `draw_queued` is called,
+/// when draws are put in the order given by `DrawQueue`.
+pub trait Renderer {
+    /// Starts a new frame, clearing everything drawn before to black.
+    fn begin_frame(&mut self);
+
+    /// Finishes the frame. Backends that batch up sprites draw whatever is left.
+    fn end_frame(&mut self);
+
+    /// Draws everything queued so far, so that whatever is drawn next goes on top of it
+    /// whichever layers they're on.
+    fn draw_queued(&mut self);
+
+    fn get_resources(&self) -> &Resources;
+
+    fn get_theme(&self) -> &Theme;
+
+    /// Switches to drawing with the theme called `name`. Unknown themes are logged and leave the
+    /// current theme in place.
+    fn set_theme(&mut self, name: &str);
+
+    /// Draws the part of the atlas in `region` as described by `params`.
+    fn draw_region(&mut self, region: Region, params: &DrawParams);
+
+    /// Draws the sprite called `name`, using whichever texture the theme draws it with.
+    fn draw_sprite(&mut self, name: &str, params: &DrawParams) {
+        let texture = self.get_theme().get_texture(name);
+        let region = self.get_resources().get_region_or_placeholder(texture);
+        self.draw_region(region, params);
+    }
+
+    /// Draws the frame that `player` is showing, cut from the animation's sprite sheet and
+    /// stretched over the sprite described by `params`. Returns `false`, drawing nothing, when
+    /// there's no animation with the player's name.
+    fn draw_animation(&mut self, player: &AnimationPlayer, params: &DrawParams) -> bool {
+        let resources = self.get_resources();
+        let animation = match resources.get_animation(player.get_name()) {
+            Some(animation) => animation,
+            None => {
+                resources.report(format!("no animation named '{}'", player.get_name()));
+                return false;
+            }
+        };
+        let texture = self.get_theme().get_texture(&animation.texture);
+        let region = resources.get_region_or_placeholder(texture);
+        let size = resources
+            .get_image(texture)
+            .map_or((1, 1), |image| image.dimensions());
+        let frame = player.get_frame_index(animation);
+        let params = DrawParams {
+            source: animation.get_source(frame, size),
+            ..*params
+        };
+        self.draw_region(region, &params);
+        true
+    }
+
+    /// Draws a line of text with its top-left corner at `position`, `height` units tall, on the UI
+    /// layer.
+    fn draw_text(&mut self, text: &str, position: Vector2<f32>, height: f32, color: Vector3<f32>) {
+        let font = self.get_resources().get_region_or_placeholder(FONT);
+        let size = Vector2::new(GLYPH_WIDTH as f32 * height / GLYPH_HEIGHT as f32, height);
+        let mut position = position;
+        for c in text.chars() {
+            if c != ' ' {
+                let params = DrawParams::new(position, size)
+                    .with_color(color)
+                    .with_layer(Layer::Ui);
+                self.draw_region(font::get_glyph(font, c), &params);
+            }
+            position[0] += font::get_advance(height);
+        }
+    }
+}
+
+impl<'a> dyn Renderer + 'a {
+    /// Fills the screen with the background, tinted by the theme.
+    pub fn render_background(&mut self) {
+        let color = self.get_theme().background;
+        self.render_sprite_by_name(
+            Layer::Background,
+            "background",
+            [0.0, 0.0],
+            [GAME_WIDTH as f32, GAME_HEIGHT as f32],
+            color,
+        );
+    }
+
+    pub fn render_sprite_by_name(
+        &mut self,
+        layer: Layer,
+        name: impl AsRef<str>,
+        position: impl Into<Vector2<f32>>,
+        size: impl Into<Vector2<f32>>,
+        color: impl Into<Vector3<f32>>,
+    ) {
+        let params = DrawParams::new(position, size)
+            .with_color(color)
+            .with_layer(layer);
+        self.draw_sprite(name.as_ref(), &params);
+    }
+
+    pub fn render_text(
+        &mut self,
+        text: impl AsRef<str>,
+        position: impl Into<Vector2<f32>>,
+        height: f32,
+        color: impl Into<Vector3<f32>>,
+    ) {
+        self.draw_text(text.as_ref(), position.into(), height, color.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_close(a: Vector2<f32>, b: [f32; 2]) {
+        assert!((a - Vector2::from(b)).norm() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn rotates_around_the_origin() {
+        let params = DrawParams {
+            rotation: FRAC_PI_2,
+            ..DrawParams::new([10.0, 20.0], [40.0, 20.0])
+        };
+        // a quarter turn clockwise around the centre, at (30, 30)
+        let corners = params.get_corners();
+        assert_close(corners[0], [40.0, 10.0]);
+        assert_close(corners[1], [40.0, 50.0]);
+        assert_close(corners[2], [20.0, 50.0]);
+        assert_close(corners[3], [20.0, 10.0]);
+
+        let params = DrawParams {
+            origin: Vector2::zeros(),
+            ..params
+        };
+        assert_close(params.to_game(Vector2::new(0.0, 0.0)), [10.0, 20.0]);
+        assert_close(params.to_game(Vector2::new(1.0, 0.0)), [10.0, 60.0]);
+    }
+
+    #[test]
+    fn flips_and_crops_the_image() {
+        let region = Region {
+            page: 0,
+            min: [0.5, 0.0],
+            max: [1.0, 0.5],
+        };
+        let params = DrawParams::new([0.0, 0.0], [1.0, 1.0]);
+        assert_eq!(
+            params.get_tex_coords(region, Vector2::new(0.0, 1.0)),
+            [0.5, 0.5]
+        );
+
+        let params = DrawParams {
+            flip_x: true,
+            source: (Vector2::new(0.5, 0.0), Vector2::new(1.0, 0.5)),
+            ..params
+        };
+        assert_eq!(
+            params.get_tex_coords(region, Vector2::new(0.0, 0.0)),
+            [1.0, 0.0]
+        );
+        assert_eq!(
+            params.get_tex_coords(region, Vector2::new(1.0, 1.0)),
+            [0.75, 0.25]
+        );
+
+        let params = DrawParams {
+            flip_y: true,
+            ..params
+        };
+        assert_eq!(
+            params.get_tex_coords(region, Vector2::new(1.0, 0.0)),
+            [0.75, 0.25]
+        );
+    }
+
+    #[test]
+    fn queue_sorts_by_layer_then_page() {
+        let region = |page| Region {
+            page,
+            min: [0.0, 0.0],
+            max: [1.0, 1.0],
+        };
+        let params = |x: f32, layer| DrawParams::new([x, 0.0], [1.0, 1.0]).with_layer(layer);
+
+        // each call tagged with the shader it was queued with
+        let mut queue = DrawQueue::default();
+        queue.push(region(1), &params(0.0, Layer::Ui), "a");
+        queue.push(region(1), &params(1.0, Layer::Bricks), "a");
+        queue.push(region(0), &params(2.0, Layer::Bricks), "a");
+        queue.push(region(1), &params(3.0, Layer::Bricks), "b");
+        queue.push(region(0), &params(4.0, Layer::Background), "b");
+        queue.push(region(0), &params(5.0, Layer::Bricks), "b");
+
+        let order: Vec<_> = queue
+            .drain()
+            .iter()
+            .map(|(_, params, shader)| (params.position[0], *shader))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (4.0, "b"),
+                (2.0, "a"),
+                (5.0, "b"),
+                (1.0, "a"),
+                (3.0, "b"),
+                (0.0, "a")
+            ]
+        );
+        assert!(queue.drain().is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use crate::input::Input;
+
+/// A recording of every tick's input, along with what's needed to start the game in the same state.
+///
+/// Consecutive identical inputs are stored as a single run to keep the files small.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub level: usize,
+    inputs: Vec<(u32, Input)>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, level: usize) -> Self {
+        Replay {
+            seed,
+            level,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn record(&mut self, input: Input) {
+        if let Some((count, last)) = self.inputs.last_mut() {
+            if *last == input {
+                *count += 1;
+                return;
+            }
+        }
+        self.inputs.push((1, input));
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = Input> + '_ {
+        self.inputs
+            .iter()
+            .flat_map(|(count, input)| (0..*count).map(move |_| *input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let left = Input {
+            left: true,
+            ..Input::default()
+        };
+        let launch = Input {
+            launch: true,
+            pointer: Some(512.0),
+            ..Input::default()
+        };
+        let recorded = [left, left, left, Input::default(), launch, launch, left];
+
+        let mut replay = Replay::new(42, 3);
+        for input in recorded.iter() {
+            replay.record(*input);
+        }
+        // runs of the same input are stored once
+        assert_eq!(replay.inputs.len(), 4);
+
+        let json = serde_json::to_string(&replay).unwrap();
+        let replay: Replay = serde_json::from_str(&json).unwrap();
+        assert_eq!((replay.seed, replay.level), (42, 3));
+        assert_eq!(replay.inputs().collect::<Vec<_>>(), recorded);
+    }
+}
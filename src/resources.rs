@@ -1,50 +1,272 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
 
-use glium::{texture::RawImage2d, Display, Program, ProgramCreationError, Texture2d};
-use image::{DynamicImage, GenericImageView, ImageBuffer, ImageError, Rgba};
+use glium::texture::{MipmapsOption, RawImage2d, TextureCreationError};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use glium::{Display, Program, ProgramCreationError, Texture2d};
+use image::{DynamicImage, ImageError, Rgba, RgbaImage};
 
-pub type Texture = ImageBuffer<Rgba<u8>, Vec<u8>>;
+use crate::animation::Animation;
+use crate::atlas::{Atlas, Region};
+use crate::theme::{Theme, DEFAULT_THEME};
+
+/// The width and height of each atlas page. Every OpenGL 3.3 implementation worth running on
+/// supports textures at least this big.
+const ATLAS_SIZE: u32 = 2048;
+
+/// The name the placeholder for missing textures is packed under. The parentheses keep it from
+/// clashing with real texture names.
+const PLACEHOLDER: &str = "(missing)";
+
+/// Anything that can go wrong while loading textures and shaders.
+#[derive(Debug)]
+pub enum ResourceError {
+    /// A file couldn't be read.
+    Io { path: PathBuf, error: io::Error },
+    /// An image couldn't be decoded.
+    Image { name: String, error: ImageError },
+    /// An atlas page couldn't be uploaded to the GPU.
+    Texture(TextureCreationError),
+    /// A shader failed to compile or link.
+    Shader {
+        name: String,
+        error: ProgramCreationError,
+    },
+    /// A shader was asked for that was never loaded.
+    MissingShader(String),
+    /// A theme definition couldn't be parsed.
+    Theme {
+        name: String,
+        error: serde_json::Error,
+    },
+    /// A file of animation definitions couldn't be parsed.
+    Animations(serde_json::Error),
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResourceError::Io { path, error } => {
+                write!(f, "failed to read {}: {}", path.display(), error)
+            }
+            ResourceError::Image { name, error } => {
+                write!(f, "failed to load image '{}': {}", name, error)
+            }
+            ResourceError::Texture(error) => write!(f, "failed to create texture: {}", error),
+            ResourceError::Shader { name, error } => {
+                write!(f, "failed to build shader '{}': {}", name, error)
+            }
+            ResourceError::MissingShader(name) => write!(f, "no shader named '{}'", name),
+            ResourceError::Theme { name, error } => {
+                write!(f, "failed to load theme '{}': {}", name, error)
+            }
+            ResourceError::Animations(error) => write!(f, "failed to load animations: {}", error),
+        }
+    }
+}
+
+impl Error for ResourceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResourceError::Io { error, .. } => Some(error),
+            ResourceError::Image { error, .. } => Some(error),
+            ResourceError::Texture(error) => Some(error),
+            ResourceError::Shader { error, .. } => Some(error),
+            ResourceError::MissingShader(_) => None,
+            ResourceError::Theme { error, .. } => Some(error),
+            ResourceError::Animations(error) => Some(error),
+        }
+    }
+}
+
+impl From<TextureCreationError> for ResourceError {
+    fn from(error: TextureCreationError) -> Self {
+        ResourceError::Texture(error)
+    }
+}
+
+/// How a texture is sampled when it's drawn at a different size to the image.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    /// Blend between neighbouring pixels, for smooth scaling.
+    #[default]
+    Linear,
+    /// Use the closest pixel, for crisp pixel art.
+    Nearest,
+}
+
+/// How a texture is filtered. Textures with different settings are packed onto different atlas
+/// pages, since the settings apply to a whole page.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextureSettings {
+    pub filter: Filter,
+    /// Whether to generate mipmaps, for textures that are drawn much smaller than they are.
+    /// Neighbouring images on the page can bleed into each other at the smallest sizes.
+    pub mipmaps: bool,
+}
+
+impl TextureSettings {
+    pub fn get_magnify_filter(self) -> MagnifySamplerFilter {
+        match self.filter {
+            Filter::Linear => MagnifySamplerFilter::Linear,
+            Filter::Nearest => MagnifySamplerFilter::Nearest,
+        }
+    }
+
+    pub fn get_minify_filter(self) -> MinifySamplerFilter {
+        match (self.filter, self.mipmaps) {
+            (Filter::Linear, false) => MinifySamplerFilter::Linear,
+            (Filter::Linear, true) => MinifySamplerFilter::LinearMipmapLinear,
+            (Filter::Nearest, false) => MinifySamplerFilter::Nearest,
+            (Filter::Nearest, true) => MinifySamplerFilter::NearestMipmapNearest,
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct Resources {
-    textures: HashMap<String, Texture2d>,
+    images: BTreeMap<String, (RgbaImage, TextureSettings)>,
+    atlas: Atlas,
+    /// How each atlas page is sampled.
+    settings: Vec<TextureSettings>,
+    /// The atlas pages on the GPU, once they've been uploaded.
+    pages: Vec<Texture2d>,
     shaders: HashMap<String, Program>,
+    themes: BTreeMap<String, Theme>,
+    default_theme: Theme,
+    animations: BTreeMap<String, Animation>,
+    /// Problems that have already been logged, so each is only reported once.
+    reported: RefCell<HashSet<String>>,
 }
 
 impl Resources {
     pub fn load_image_from_memory(
         &mut self,
-        display: &Display,
         name: impl AsRef<str>,
         buffer: &[u8],
-        alpha: bool,
-    ) -> Result<(), ImageError> {
-        let image = image::load_from_memory(buffer)?;
-        self.load_image(display, name, image, alpha);
+        settings: TextureSettings,
+    ) -> Result<(), ResourceError> {
+        let image = image::load_from_memory(buffer).map_err(|error| ResourceError::Image {
+            name: name.as_ref().to_owned(),
+            error,
+        })?;
+        self.load_image(name, image, settings);
         Ok(())
     }
 
+    /// Adds an image to be packed into the atlas the next time `pack` is called.
+    ///
+    /// Images of any colour type are converted to 8-bit RGBA. Images without an alpha channel are
+    /// opaque. Paletted and 16-bit PNGs are already expanded to 8 bits per channel when they're
+    /// decoded.
     pub fn load_image(
         &mut self,
-        display: &Display,
         name: impl AsRef<str>,
         image: DynamicImage,
-        alpha: bool,
+        settings: TextureSettings,
     ) {
         let name = name.as_ref().to_owned();
-        let dimensions = image.dimensions();
-        let image = if alpha {
-            RawImage2d::from_raw_rgba_reversed(&image.raw_pixels(), dimensions)
-        } else {
-            RawImage2d::from_raw_rgb_reversed(&image.raw_pixels(), dimensions)
+        let image = match image {
+            DynamicImage::ImageRgba8(image) => image,
+            DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageBgr8(_)
+            | DynamicImage::ImageBgra8(_) => image.to_rgba(),
         };
-        // TODO: don't unwrap
-        let texture = Texture2d::new(display, image).unwrap();
-        self.textures.insert(name, texture);
+        self.images.insert(name, (image, settings));
+    }
+
+    /// Returns the pixels of an image that has been loaded, whether or not it's been packed yet.
+    pub fn get_image(&self, name: impl AsRef<str>) -> Option<&RgbaImage> {
+        self.images.get(name.as_ref()).map(|(image, _)| image)
+    }
+
+    /// Packs every loaded image into the atlas, replacing the old atlas. The new atlas has to be
+    /// uploaded before it can be drawn on the GPU.
+    pub fn pack(&mut self) {
+        self.images
+            .entry(PLACEHOLDER.to_owned())
+            .or_insert_with(|| (placeholder_image(), TextureSettings::default()));
+
+        let mut groups: BTreeMap<TextureSettings, BTreeMap<String, RgbaImage>> = BTreeMap::new();
+        for (name, (image, settings)) in self.images.iter() {
+            let group = groups.entry(*settings).or_default();
+            group.insert(name.clone(), image.clone());
+        }
+
+        let mut atlas = Atlas::default();
+        let mut page_settings = Vec::new();
+        for (settings, images) in groups {
+            let group = Atlas::pack(&images, ATLAS_SIZE);
+            page_settings.extend(group.pages().iter().map(|_| settings));
+            atlas.append(group);
+        }
+        self.atlas = atlas;
+        self.settings = page_settings;
+        self.pages.clear();
     }
 
-    pub fn get_texture(&self, name: impl AsRef<str>) -> Option<&Texture2d> {
-        self.textures.get(name.as_ref())
+    /// Uploads the atlas pages to the GPU, replacing any that were uploaded before.
+    pub fn upload(&mut self, display: &Display) -> Result<(), ResourceError> {
+        let mut pages = Vec::new();
+        for (page, settings) in self.atlas.pages().iter().zip(self.settings.iter()) {
+            let mipmaps = if settings.mipmaps {
+                MipmapsOption::AutoGeneratedMipmaps
+            } else {
+                MipmapsOption::NoMipmap
+            };
+            let dimensions = page.dimensions();
+            let image = RawImage2d::from_raw_rgba(page.clone().into_raw(), dimensions);
+            pages.push(Texture2d::with_mipmaps(display, image, mipmaps)?);
+        }
+        self.pages = pages;
+        Ok(())
+    }
+
+    pub fn get_atlas(&self) -> &Atlas {
+        &self.atlas
+    }
+
+    /// Returns where the image called `name` was packed, if it has been.
+    pub fn get_region(&self, name: impl AsRef<str>) -> Option<Region> {
+        self.atlas.get_region(name)
+    }
+
+    /// Returns where the image called `name` was packed, or where the placeholder was if it
+    /// wasn't. Missing images are logged the first time they're asked for.
+    ///
+    /// Tests panic instead, so that the goldens can't quietly pass with a placeholder in them.
+    /// The game itself never does, since with `--watch` a theme or animation can name a texture
+    /// that doesn't exist at any moment.
+    pub fn get_region_or_placeholder(&self, name: impl AsRef<str>) -> Region {
+        let name = name.as_ref();
+        if let Some(region) = self.get_region(name) {
+            return region;
+        }
+        if cfg!(test) {
+            panic!("no texture named '{}'", name);
+        }
+        self.report(format!("no texture named '{}', using a placeholder", name));
+        self.get_region(PLACEHOLDER)
+            .expect("the placeholder is packed with every atlas")
+    }
+
+    /// Returns an atlas page's texture, along with how it should be sampled, if it's been
+    /// uploaded.
+    pub fn get_page(&self, page: usize) -> Option<(&Texture2d, TextureSettings)> {
+        Some((self.pages.get(page)?, *self.settings.get(page)?))
+    }
+
+    /// Returns an atlas page's pixels, along with how it should be sampled.
+    pub fn get_page_image(&self, page: usize) -> Option<(&RgbaImage, TextureSettings)> {
+        Some((self.atlas.pages().get(page)?, *self.settings.get(page)?))
     }
 
     pub fn load_shader(
@@ -53,14 +275,171 @@ impl Resources {
         name: impl AsRef<str>,
         vertex: &str,
         fragment: &str,
-    ) -> Result<(), ProgramCreationError> {
+    ) -> Result<(), ResourceError> {
         let name = name.as_ref().to_owned();
-        let program = Program::from_source(display, vertex, fragment, None)?;
-        self.shaders.insert(name, program);
-        Ok(())
+        match Program::from_source(display, vertex, fragment, None) {
+            Ok(program) => {
+                self.shaders.insert(name, program);
+                Ok(())
+            }
+            Err(error) => Err(ResourceError::Shader { name, error }),
+        }
+    }
+
+    pub fn get_shader(&self, name: impl AsRef<str>) -> Result<&Program, ResourceError> {
+        let name = name.as_ref();
+        self.shaders
+            .get(name)
+            .ok_or_else(|| ResourceError::MissingShader(name.to_owned()))
+    }
+
+    pub fn load_theme(&mut self, name: impl AsRef<str>, theme: Theme) {
+        self.themes.insert(name.as_ref().to_owned(), theme);
+    }
+
+    /// Returns the theme called `name`. The default theme is always available, even if it hasn't
+    /// been loaded.
+    pub fn get_theme(&self, name: impl AsRef<str>) -> Option<&Theme> {
+        let name = name.as_ref();
+        match self.themes.get(name) {
+            Some(theme) => Some(theme),
+            None if name == DEFAULT_THEME => Some(&self.default_theme),
+            None => None,
+        }
+    }
+
+    /// Returns the names of every available theme, in alphabetical order.
+    pub fn get_theme_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.themes.keys().map(String::as_str).collect();
+        if !self.themes.contains_key(DEFAULT_THEME) {
+            names.push(DEFAULT_THEME);
+            names.sort();
+        }
+        names
+    }
+
+    pub fn load_animation(&mut self, name: impl AsRef<str>, animation: Animation) {
+        self.animations.insert(name.as_ref().to_owned(), animation);
+    }
+
+    pub fn get_animation(&self, name: impl AsRef<str>) -> Option<&Animation> {
+        self.animations.get(name.as_ref())
     }
 
-    pub fn get_shader(&self, name: impl AsRef<str>) -> Option<&Program> {
-        self.shaders.get(name.as_ref())
+    /// Logs `message` unless it's already been logged, for problems noticed while rendering
+    /// that would otherwise be reported every frame.
+    pub fn report(&self, message: String) {
+        if self.reported.borrow_mut().insert(message.clone()) {
+            eprintln!("{}", message);
+        }
+    }
+}
+
+/// A magenta and black checkerboard that stands in for textures that failed to load.
+fn placeholder_image() -> RgbaImage {
+    RgbaImage::from_fn(16, 16, |x, y| {
+        if (x / 8 + y / 8) % 2 == 0 {
+            Rgba([255, 0, 255, 255])
+        } else {
+            Rgba([0, 0, 0, 255])
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholder_is_a_checkerboard() {
+        let image = placeholder_image();
+        let magenta = Rgba([255, 0, 255, 255]);
+        let black = Rgba([0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(0, 0), &magenta);
+        assert_eq!(image.get_pixel(8, 0), &black);
+        assert_eq!(image.get_pixel(0, 8), &black);
+        assert_eq!(image.get_pixel(15, 15), &magenta);
+    }
+
+    #[test]
+    fn bad_images_are_reported_by_name() {
+        let mut resources = Resources::default();
+        let err = resources
+            .load_image_from_memory("paddle", b"not an image", TextureSettings::default())
+            .unwrap_err();
+        assert!(err.to_string().starts_with("failed to load image 'paddle'"));
+        assert!(err.source().is_some());
+        assert!(resources.get_region("paddle").is_none());
+    }
+
+    fn load_fixture(buffer: &[u8]) -> Vec<[u8; 4]> {
+        let mut resources = Resources::default();
+        resources
+            .load_image_from_memory("fixture", buffer, TextureSettings::default())
+            .unwrap();
+        let image = resources.get_image("fixture").unwrap();
+        image.pixels().map(|pixel| pixel.data).collect()
+    }
+
+    #[test]
+    fn converts_every_colour_type_to_rgba() {
+        let cases: [(&[u8], [[u8; 4]; 2]); 7] = [
+            (
+                include_bytes!("../tests/fixtures/rgb8.png"),
+                [[1, 2, 3, 255], [4, 5, 6, 255]],
+            ),
+            (
+                include_bytes!("../tests/fixtures/gray8.png"),
+                [[0, 0, 0, 255], [200, 200, 200, 255]],
+            ),
+            (
+                include_bytes!("../tests/fixtures/gray_alpha8.png"),
+                [[200, 200, 200, 128], [50, 50, 50, 255]],
+            ),
+            (
+                include_bytes!("../tests/fixtures/gray16.png"),
+                [[0x12, 0x12, 0x12, 255], [0xC8, 0xC8, 0xC8, 255]],
+            ),
+            (
+                include_bytes!("../tests/fixtures/rgb16.png"),
+                [[255, 128, 0, 255], [0, 64, 255, 255]],
+            ),
+            (
+                include_bytes!("../tests/fixtures/rgba16.png"),
+                [[255, 0, 0, 128], [16, 32, 48, 255]],
+            ),
+            (
+                include_bytes!("../tests/fixtures/palette.png"),
+                [[255, 0, 255, 0], [10, 20, 30, 255]],
+            ),
+        ];
+        for (i, (buffer, pixels)) in cases.iter().enumerate() {
+            assert_eq!(load_fixture(buffer), pixels.to_vec(), "case {}", i);
+        }
+    }
+
+    #[test]
+    fn shipped_textures_keep_their_transparency() {
+        let ball = load_fixture(include_bytes!("../textures/ball.png"));
+        assert!(ball.iter().any(|pixel| pixel[3] == 0));
+        let block = load_fixture(include_bytes!("../textures/block.png"));
+        assert!(block.iter().all(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn settings_pick_sampler_filters() {
+        let settings = TextureSettings {
+            filter: Filter::Nearest,
+            mipmaps: true,
+        };
+        assert_eq!(
+            settings.get_minify_filter(),
+            MinifySamplerFilter::NearestMipmapNearest
+        );
+        assert_eq!(settings.get_magnify_filter(), MagnifySamplerFilter::Nearest);
+        assert_eq!(
+            TextureSettings::default().get_minify_filter(),
+            MinifySamplerFilter::Linear
+        );
     }
 }
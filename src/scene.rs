@@ -0,0 +1,308 @@
+use std::fmt;
+use std::time::Duration;
+
+use glium::glutin::Event;
+
+use crate::input::{Input, InputState};
+use crate::level::Level;
+use crate::renderer::Renderer;
+
+/// What a scene asks the stack to do next.
+pub enum Transition {
+    None,
+    /// Puts a scene on top of this one, which stays underneath until it's uncovered.
+    Push(Box<dyn Scene>),
+    /// Removes this scene, uncovering the one underneath.
+    Pop,
+    /// Swaps this scene for another.
+    // nothing swaps scenes yet, until there's a menu to start playing from
+    #[allow(dead_code)]
+    Replace(Box<dyn Scene>),
+}
+
+/// One screen of the game, like playing a level, the editor or the pause overlay.
+///
+/// Only the scene on top of the stack handles input and is updated. Scenes underneath keep their
+/// state until they're uncovered, and still show through overlays. `Display` describes the
+/// scene's state for headless runs.
+pub trait Scene: fmt::Display {
+    /// A short name for the scene, for headless output.
+    fn get_name(&self) -> &str;
+
+    /// Called when the scene comes to the top of the stack, whether it was just added or the
+    /// scene above it was removed.
+    fn on_enter(&mut self) {}
+
+    /// Called when the scene stops being on top of the stack, whether it's being removed or
+    /// covered by another scene.
+    fn on_exit(&mut self) {}
+
+    /// Handles window events that aren't part of the recorded `Input`, like the editor's mouse
+    /// painting. Anything that changes the simulation has to come through `update` instead, so
+    /// that replays see it.
+    fn handle_input(&mut self, _event: &Event, _input: &InputState) -> Transition {
+        Transition::None
+    }
+
+    /// Advances the scene by one tick.
+    fn update(&mut self, input: &Input, delta: Duration) -> Transition;
+
+    fn render(&self, renderer: &mut dyn Renderer);
+
+    /// Whether the scene is drawn over the scene underneath rather than hiding it.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    /// Swaps in a new set of levels, for scenes that play the level files.
+    fn reload_levels(&mut self, _levels: Vec<Level>) {}
+}
+
+/// The scenes the game is showing, from the bottom up.
+#[derive(Default)]
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+}
+
+impl SceneStack {
+    pub fn push(&mut self, mut scene: Box<dyn Scene>) {
+        if let Some(top) = self.scenes.last_mut() {
+            top.on_exit();
+        }
+        scene.on_enter();
+        self.scenes.push(scene);
+    }
+
+    pub fn pop(&mut self) -> Option<Box<dyn Scene>> {
+        let mut scene = self.scenes.pop()?;
+        scene.on_exit();
+        if let Some(top) = self.scenes.last_mut() {
+            top.on_enter();
+        }
+        Some(scene)
+    }
+
+    pub fn replace(&mut self, mut scene: Box<dyn Scene>) -> Option<Box<dyn Scene>> {
+        let old = self.scenes.pop().map(|mut old| {
+            old.on_exit();
+            old
+        });
+        scene.on_enter();
+        self.scenes.push(scene);
+        old
+    }
+
+    fn apply(&mut self, transition: Transition) {
+        match transition {
+            Transition::None => (),
+            Transition::Push(scene) => self.push(scene),
+            Transition::Pop => {
+                self.pop();
+            }
+            Transition::Replace(scene) => {
+                self.replace(scene);
+            }
+        }
+    }
+
+    /// Iterates over the scenes from the bottom of the stack up.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Scene> {
+        self.scenes.iter().map(|scene| scene.as_ref())
+    }
+
+    /// Returns the scene at the bottom of the stack, the one the game started with.
+    pub fn get_bottom_mut(&mut self) -> Option<&mut dyn Scene> {
+        match self.scenes.first_mut() {
+            Some(scene) => Some(scene.as_mut()),
+            None => None,
+        }
+    }
+
+    /// Lets the top scene handle a window event. Clicks and presses still waiting for the next
+    /// tick were meant for that scene, so they're dropped if it changes the stack.
+    pub fn handle_input(&mut self, event: &Event, input: &mut InputState) {
+        if let Some(top) = self.scenes.last_mut() {
+            let transition = top.handle_input(event, input);
+            if let Transition::None = transition {
+                return;
+            }
+            input.clear_pending();
+            self.apply(transition);
+        }
+    }
+
+    pub fn update(&mut self, input: &Input, delta: Duration) {
+        if let Some(top) = self.scenes.last_mut() {
+            let transition = top.update(input, delta);
+            self.apply(transition);
+        }
+    }
+
+    /// Draws the top scene, over the scenes it's an overlay for. Everything each scene draws
+    /// goes over everything the scenes below it drew.
+    pub fn render(&self, renderer: &mut dyn Renderer) {
+        let first = self
+            .scenes
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+        for (i, scene) in self.scenes.iter().enumerate().skip(first) {
+            if i > first {
+                renderer.draw_queued();
+            }
+            scene.render(renderer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::resources::Resources;
+    use crate::software::SoftwareRenderer;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// A scene that logs everything that happens to it and does whatever it's told on its next
+    /// update.
+    struct Fake {
+        name: &'static str,
+        overlay: bool,
+        log: Log,
+        next: Option<Transition>,
+    }
+
+    impl Fake {
+        fn new(name: &'static str, log: &Log) -> Box<Self> {
+            Box::new(Fake {
+                name,
+                overlay: false,
+                log: log.clone(),
+                next: None,
+            })
+        }
+
+        fn overlay(name: &'static str, log: &Log) -> Box<Self> {
+            Box::new(Fake {
+                overlay: true,
+                ..*Fake::new(name, log)
+            })
+        }
+
+        fn then(mut self: Box<Self>, transition: Transition) -> Box<Self> {
+            self.next = Some(transition);
+            self
+        }
+
+        fn record(&self, event: &str) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {}", self.name, event));
+        }
+    }
+
+    impl fmt::Display for Fake {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.name)
+        }
+    }
+
+    impl Scene for Fake {
+        fn get_name(&self) -> &str {
+            self.name
+        }
+
+        fn on_enter(&mut self) {
+            self.record("enter");
+        }
+
+        fn on_exit(&mut self) {
+            self.record("exit");
+        }
+
+        fn update(&mut self, _input: &Input, _delta: Duration) -> Transition {
+            self.record("update");
+            self.next.take().unwrap_or(Transition::None)
+        }
+
+        fn render(&self, _renderer: &mut dyn Renderer) {
+            self.record("render");
+        }
+
+        fn is_overlay(&self) -> bool {
+            self.overlay
+        }
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        log.borrow_mut().drain(..).collect()
+    }
+
+    fn tick(stack: &mut SceneStack) {
+        stack.update(&Input::default(), Duration::from_millis(10));
+    }
+
+    fn names(stack: &SceneStack) -> Vec<&str> {
+        stack.iter().map(|scene| scene.get_name()).collect()
+    }
+
+    #[test]
+    fn only_the_top_scene_is_updated() {
+        let log = Log::default();
+        let mut stack = SceneStack::default();
+        stack.push(Fake::new("a", &log));
+        stack.push(Fake::new("b", &log));
+        tick(&mut stack);
+        assert_eq!(take(&log), ["a enter", "a exit", "b enter", "b update"]);
+        assert_eq!(names(&stack), ["a", "b"]);
+    }
+
+    #[test]
+    fn scenes_push_pop_and_replace_themselves() {
+        let log = Log::default();
+        let mut stack = SceneStack::default();
+        let menu = Fake::new("menu", &log).then(Transition::Replace(Fake::new("play", &log).then(
+            Transition::Push(Fake::new("pause", &log).then(Transition::Pop)),
+        )));
+        stack.push(menu);
+        take(&log);
+
+        tick(&mut stack);
+        assert_eq!(take(&log), ["menu update", "menu exit", "play enter"]);
+        assert_eq!(names(&stack), ["play"]);
+
+        tick(&mut stack);
+        assert_eq!(take(&log), ["play update", "play exit", "pause enter"]);
+        assert_eq!(names(&stack), ["play", "pause"]);
+
+        tick(&mut stack);
+        assert_eq!(take(&log), ["pause update", "pause exit", "play enter"]);
+        assert_eq!(names(&stack), ["play"]);
+
+        assert!(stack.pop().is_some());
+        assert!(stack.pop().is_none());
+        assert!(names(&stack).is_empty());
+        tick(&mut stack);
+    }
+
+    #[test]
+    fn overlays_are_drawn_over_the_scene_underneath() {
+        let mut resources = Resources::default();
+        resources.pack();
+        let mut renderer = SoftwareRenderer::new(&resources);
+
+        let log = Log::default();
+        let mut stack = SceneStack::default();
+        stack.push(Fake::new("hidden", &log));
+        stack.push(Fake::new("play", &log));
+        stack.push(Fake::overlay("pause", &log));
+        stack.push(Fake::overlay("dialog", &log));
+        take(&log);
+
+        stack.render(&mut renderer);
+        assert_eq!(take(&log), ["play render", "pause render", "dialog render"]);
+    }
+}
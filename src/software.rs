@@ -0,0 +1,383 @@
+use image::{Rgba, RgbaImage};
+use nalgebra::Vector2;
+
+use crate::atlas::Region;
+use crate::renderer::{DrawParams, DrawQueue, Renderer};
+use crate::resources::{Filter, Resources};
+use crate::theme::{Theme, DEFAULT_THEME};
+use crate::{GAME_HEIGHT, GAME_WIDTH};
+
+/// Draws the game into an image in memory without needing a GPU, at `scale` pixels per game
+/// unit.
+///
+/// It follows the sprite shader closely enough for screenshots and tests: a pixel is drawn when
+/// its centre is inside a sprite, textures are sampled with their page's filter, and sprites are
+/// alpha blended over what's underneath. Theme shaders and mipmaps aren't supported.
+pub struct SoftwareRenderer<'a> {
+    resources: &'a Resources,
+    theme: &'a Theme,
+    scale: f32,
+    image: RgbaImage,
+    queue: DrawQueue,
+}
+
+impl<'a> SoftwareRenderer<'a> {
+    pub fn new(resources: &'a Resources) -> Self {
+        SoftwareRenderer::with_scale(resources, 1.0)
+    }
+
+    /// Creates a renderer that draws the game `scale` times its usual size, for smaller images.
+    pub fn with_scale(resources: &'a Resources, scale: f32) -> Self {
+        let width = (GAME_WIDTH as f32 * scale).round() as u32;
+        let height = (GAME_HEIGHT as f32 * scale).round() as u32;
+        SoftwareRenderer {
+            resources,
+            theme: resources.get_theme(DEFAULT_THEME).unwrap(),
+            scale,
+            image: RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255])),
+            queue: DrawQueue::default(),
+        }
+    }
+
+    /// Returns what's been drawn so far. Sprites only show up once the frame has ended.
+    pub fn get_image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// Draws a sprite into the image straight away.
+    fn rasterize(&mut self, region: Region, params: &DrawParams) {
+        let (page, settings) = match self.resources.get_page_image(region.page) {
+            Some(page) => page,
+            None => return,
+        };
+
+        // only the pixels around the sprite's corners need checking
+        let corners = params.get_corners();
+        let (mut min, mut max) = (corners[0], corners[0]);
+        for corner in corners.iter() {
+            min = Vector2::new(min[0].min(corner[0]), min[1].min(corner[1]));
+            max = Vector2::new(max[0].max(corner[0]), max[1].max(corner[1]));
+        }
+        let (width, height) = self.image.dimensions();
+        let (min, max) = (min * self.scale, max * self.scale);
+        let first = |min: f32| (min - 0.5).ceil().max(0.0) as u32;
+        let last = |max: f32, limit: u32| ((max - 0.5).ceil().max(0.0) as u32).min(limit);
+
+        // maps a point in game space back onto the sprite, as a fraction of its size, undoing
+        // `params.to_game`
+        let (sin, cos) = (-params.rotation).sin_cos();
+        let pivot = params.origin.component_mul(&params.size);
+        let to_sprite = |x: f32, y: f32| {
+            let (x, y) = (
+                x - params.position[0] - pivot[0],
+                y - params.position[1] - pivot[1],
+            );
+            Vector2::new(
+                (cos * x - sin * y + pivot[0]) / params.size[0],
+                (sin * x + cos * y + pivot[1]) / params.size[1],
+            )
+        };
+
+        for y in first(min[1])..last(max[1], height) {
+            for x in first(min[0])..last(max[0], width) {
+                // a pixel is drawn when its centre is on the sprite
+                let point = to_sprite((x as f32 + 0.5) / self.scale, (y as f32 + 0.5) / self.scale);
+                if point[0] < 0.0 || point[0] >= 1.0 || point[1] < 0.0 || point[1] >= 1.0 {
+                    continue;
+                }
+
+                let [u, v] = params.get_tex_coords(region, point);
+                let texel = match settings.filter {
+                    Filter::Linear => sample_linear(page, u, v),
+                    Filter::Nearest => sample_nearest(page, u, v),
+                };
+                let tint = params.tint;
+                let source = [
+                    texel[0] * tint[0],
+                    texel[1] * tint[1],
+                    texel[2] * tint[2],
+                    texel[3] * tint[3],
+                ];
+                let pixel = self.image.get_pixel_mut(x, y);
+                *pixel = blend(source, *pixel);
+            }
+        }
+    }
+}
+
+impl<'a> Renderer for SoftwareRenderer<'a> {
+    fn begin_frame(&mut self) {
+        self.queue.clear();
+        for pixel in self.image.pixels_mut() {
+            *pixel = Rgba([0, 0, 0, 255]);
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.draw_queued();
+    }
+
+    fn draw_queued(&mut self) {
+        for (region, params, ()) in self.queue.drain() {
+            self.rasterize(region, &params);
+        }
+    }
+
+    fn get_resources(&self) -> &Resources {
+        self.resources
+    }
+
+    fn get_theme(&self) -> &Theme {
+        self.theme
+    }
+
+    fn set_theme(&mut self, name: &str) {
+        match self.resources.get_theme(name) {
+            Some(theme) => self.theme = theme,
+            None => self.resources.report(format!("no theme named '{}'", name)),
+        }
+    }
+
+    fn draw_region(&mut self, region: Region, params: &DrawParams) {
+        self.queue.push(region, params, ());
+    }
+}
+
+/// Returns the colour of the texel at `x`, `y`, clamped to the edge of the page, with channels
+/// from 0 to 1.
+fn get_texel(page: &RgbaImage, x: i64, y: i64) -> [f32; 4] {
+    let x = x.clamp(0, page.width() as i64 - 1) as u32;
+    let y = y.clamp(0, page.height() as i64 - 1) as u32;
+    let pixel = page.get_pixel(x, y).data;
+    [
+        pixel[0] as f32 / 255.0,
+        pixel[1] as f32 / 255.0,
+        pixel[2] as f32 / 255.0,
+        pixel[3] as f32 / 255.0,
+    ]
+}
+
+fn sample_nearest(page: &RgbaImage, u: f32, v: f32) -> [f32; 4] {
+    let x = (u * page.width() as f32).floor() as i64;
+    let y = (v * page.height() as f32).floor() as i64;
+    get_texel(page, x, y)
+}
+
+/// Blends the four texels nearest to `u`, `v` by how close their centres are.
+fn sample_linear(page: &RgbaImage, u: f32, v: f32) -> [f32; 4] {
+    let x = u * page.width() as f32 - 0.5;
+    let y = v * page.height() as f32 - 0.5;
+    let (left, top) = (x.floor(), y.floor());
+    let (tx, ty) = (x - left, y - top);
+    let (left, top) = (left as i64, top as i64);
+
+    let (a, b) = (get_texel(page, left, top), get_texel(page, left + 1, top));
+    let (c, d) = (
+        get_texel(page, left, top + 1),
+        get_texel(page, left + 1, top + 1),
+    );
+    let mut texel = [0.0; 4];
+    for i in 0..4 {
+        let upper = a[i] + (b[i] - a[i]) * tx;
+        let lower = c[i] + (d[i] - c[i]) * tx;
+        texel[i] = upper + (lower - upper) * ty;
+    }
+    texel
+}
+
+/// Draws `source` over `target`, letting `target` show through where `source` is transparent.
+fn blend(source: [f32; 4], target: Rgba<u8>) -> Rgba<u8> {
+    let alpha = source[3];
+    let mut pixel = [0; 4];
+    for i in 0..3 {
+        let value = source[i] * alpha + target.data[i] as f32 / 255.0 * (1.0 - alpha);
+        pixel[i] = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    let value = alpha + target.data[3] as f32 / 255.0 * (1.0 - alpha);
+    pixel[3] = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgba(pixel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::Layer;
+    use crate::resources::TextureSettings;
+    use image::DynamicImage;
+
+    /// Returns resources with a 2x2 image of a red, green, blue and transparent pixel, drawn
+    /// with nearest filtering so that each stays distinct.
+    fn resources() -> Resources {
+        let image = RgbaImage::from_raw(
+            2,
+            2,
+            vec![
+                255, 0, 0, 255, 0, 255, 0, 255, //
+                0, 0, 255, 255, 0, 0, 0, 0,
+            ],
+        )
+        .unwrap();
+        let settings = TextureSettings {
+            filter: Filter::Nearest,
+            mipmaps: false,
+        };
+        let mut resources = Resources::default();
+        resources.load_image("quad", DynamicImage::ImageRgba8(image), settings);
+        resources.load_image(
+            "font",
+            DynamicImage::ImageRgba8(crate::font::font_image()),
+            settings,
+        );
+        resources.pack();
+        resources
+    }
+
+    fn pixel(renderer: &SoftwareRenderer, x: u32, y: u32) -> [u8; 4] {
+        renderer.get_image().get_pixel(x, y).data
+    }
+
+    #[test]
+    fn draws_sprites_the_right_way_up() {
+        let resources = resources();
+        let mut renderer = SoftwareRenderer::new(&resources);
+        renderer.begin_frame();
+        renderer.draw_sprite("quad", &DrawParams::new([10.0, 20.0], [4.0, 4.0]));
+        renderer.end_frame();
+
+        assert_eq!(pixel(&renderer, 10, 20), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 13, 20), [0, 255, 0, 255]);
+        assert_eq!(pixel(&renderer, 10, 23), [0, 0, 255, 255]);
+        // the transparent corner leaves the black background showing
+        assert_eq!(pixel(&renderer, 13, 23), [0, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 9, 20), [0, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 14, 24), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn tints_and_blends_sprites() {
+        let resources = resources();
+        let mut renderer = SoftwareRenderer::new(&resources);
+        renderer.begin_frame();
+        let params = DrawParams::new([0.0, 0.0], [2.0, 2.0]);
+        renderer.draw_sprite("quad", &params.with_color([0.5, 1.0, 1.0]));
+        renderer.end_frame();
+        assert_eq!(pixel(&renderer, 0, 0), [128, 0, 0, 255]);
+
+        // drawing only the transparent corner changes nothing
+        let quad = resources.get_region("quad").unwrap();
+        let corner = Region {
+            min: [
+                (quad.min[0] + quad.max[0]) / 2.0,
+                (quad.min[1] + quad.max[1]) / 2.0,
+            ],
+            ..quad
+        };
+        renderer.draw_region(corner, &params);
+        renderer.end_frame();
+        assert_eq!(pixel(&renderer, 0, 0), [128, 0, 0, 255]);
+
+        // half transparent sprites let half of what's underneath through
+        renderer.begin_frame();
+        let faded = DrawParams {
+            tint: [1.0, 1.0, 1.0, 0.5].into(),
+            ..params
+        };
+        renderer.draw_sprite("quad", &faded);
+        renderer.end_frame();
+        assert_eq!(pixel(&renderer, 0, 0), [128, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 1, 0), [0, 128, 0, 255]);
+    }
+
+    #[test]
+    fn flips_and_rotates_sprites() {
+        let resources = resources();
+        let mut renderer = SoftwareRenderer::new(&resources);
+        let params = DrawParams::new([0.0, 0.0], [2.0, 2.0]);
+        let flipped = DrawParams {
+            flip_x: true,
+            ..params
+        };
+        renderer.draw_sprite("quad", &flipped);
+        renderer.end_frame();
+        assert_eq!(pixel(&renderer, 0, 0), [0, 255, 0, 255]);
+        assert_eq!(pixel(&renderer, 1, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 0, 1), [0, 0, 0, 255]);
+
+        // a quarter turn clockwise brings the bottom-left corner to the top-left
+        renderer.begin_frame();
+        let rotated = DrawParams {
+            rotation: std::f32::consts::FRAC_PI_2,
+            ..params
+        };
+        renderer.draw_sprite("quad", &rotated);
+        renderer.end_frame();
+        assert_eq!(pixel(&renderer, 0, 0), [0, 0, 255, 255]);
+        assert_eq!(pixel(&renderer, 1, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 1, 1), [0, 255, 0, 255]);
+        assert_eq!(pixel(&renderer, 0, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn only_covers_pixels_whose_centres_are_inside() {
+        let resources = resources();
+        let mut renderer = SoftwareRenderer::new(&resources);
+        renderer.begin_frame();
+        renderer.draw_sprite("quad", &DrawParams::new([0.6, 0.6], [2.0, 2.0]));
+        renderer.end_frame();
+        assert_eq!(pixel(&renderer, 0, 0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 1, 1), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 2, 1), [0, 255, 0, 255]);
+        assert_eq!(pixel(&renderer, 3, 1), [0, 0, 0, 255]);
+
+        // sprites hanging off the edge are clipped
+        renderer.draw_sprite("quad", &DrawParams::new([-1.0, -1.0], [4.0, 4.0]));
+        renderer.end_frame();
+        assert_eq!(pixel(&renderer, 0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 2, 0), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn draws_text() {
+        let resources = resources();
+        let mut renderer = SoftwareRenderer::new(&resources);
+        renderer.begin_frame();
+        renderer.draw_text("-", [0.0, 0.0].into(), 7.0, [1.0; 3].into());
+        renderer.end_frame();
+        // the dash is the fourth row of the glyph
+        assert_eq!(pixel(&renderer, 0, 3), [255; 4]);
+        assert_eq!(pixel(&renderer, 4, 3), [255; 4]);
+        assert_eq!(pixel(&renderer, 0, 2), [0, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 5, 3), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn scales_everything_it_draws() {
+        let resources = resources();
+        let mut renderer = SoftwareRenderer::with_scale(&resources, 0.5);
+        assert_eq!(
+            renderer.get_image().dimensions(),
+            (GAME_WIDTH / 2, GAME_HEIGHT / 2)
+        );
+        renderer.begin_frame();
+        renderer.draw_sprite("quad", &DrawParams::new([20.0, 40.0], [8.0, 8.0]));
+        renderer.end_frame();
+        assert_eq!(pixel(&renderer, 10, 20), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 13, 23), [0, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 14, 20), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draws_later_layers_on_top() {
+        let resources = resources();
+        let mut renderer = SoftwareRenderer::new(&resources);
+        renderer.begin_frame();
+        let params = DrawParams::new([0.0, 0.0], [2.0, 2.0]);
+        let ui = params.with_color([0.5, 1.0, 1.0]).with_layer(Layer::Ui);
+        let background = params.with_color([0.0; 3]).with_layer(Layer::Background);
+        renderer.draw_sprite("quad", &ui);
+        renderer.draw_sprite("quad", &params);
+        renderer.draw_sprite("quad", &background);
+        renderer.end_frame();
+        assert_eq!(pixel(&renderer, 0, 0), [128, 0, 0, 255]);
+    }
+}
@@ -1,115 +1,184 @@
 use glium::draw_parameters::{Blend, DrawParameters};
 use glium::index::{NoIndices, PrimitiveType};
-use glium::{Display, Frame, Program, Surface, Texture2d, VertexBuffer};
-use nalgebra::{Matrix4, Vector2, Vector3};
+use glium::{Display, Frame, Program, Surface, VertexBuffer};
+use nalgebra::Vector2;
 
-use crate::game::Game;
-use crate::resources::Resources;
+use crate::atlas::Region;
+use crate::renderer::{DrawParams, DrawQueue, Renderer};
+use crate::resources::{ResourceError, Resources};
+use crate::theme::{Theme, DEFAULT_THEME};
+use crate::viewport::Viewport;
+use crate::{GAME_HEIGHT, GAME_WIDTH};
 
+#[derive(Copy, Clone)]
+struct Vertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    tint: [f32; 4],
+}
+
+implement_vertex!(Vertex, position, tex_coords, tint);
+
+/// Draws sprites from the texture atlas on the GPU, in the style of the current theme. The atlas
+/// has to have been uploaded.
+///
+/// Sprites are queued until the end of the frame and then put in order, so that consecutive
+/// sprites from the same atlas page and shader can be drawn together in one batch. Each sprite is
+/// queued with the shader of the theme it was drawn in, so themes can change partway through a
+/// frame without upsetting the order.
 pub struct SpriteRenderer<'a, 'b> {
     target: &'a mut Frame,
     display: &'b Display,
     program: &'b Program,
     resources: &'b Resources,
+    theme: &'b Theme,
+    viewport: Viewport,
+    queue: DrawQueue<&'b Program>,
+    batch: Vec<Vertex>,
+    page: usize,
+    /// The shader the batch is drawn with.
+    batch_program: &'b Program,
 }
 
 impl<'a, 'b> SpriteRenderer<'a, 'b> {
-    pub fn new(game: &'b Game, target: &'a mut Frame) -> Self {
-        let program = game.resources.get_shader("sprite").unwrap();
-        let display = &game.display;
-        let resources = &game.resources;
-        SpriteRenderer {
+    pub fn new(
+        display: &'b Display,
+        resources: &'b Resources,
+        target: &'a mut Frame,
+    ) -> Result<Self, ResourceError> {
+        let program = resources.get_shader("sprite")?;
+        let theme = resources.get_theme(DEFAULT_THEME).unwrap();
+        let viewport = Viewport::fit(target.get_dimensions());
+        Ok(SpriteRenderer {
             target,
             program,
             display,
             resources,
-        }
+            theme,
+            viewport,
+            queue: DrawQueue::default(),
+            batch: Vec::new(),
+            page: 0,
+            batch_program: program,
+        })
     }
 
-    pub fn render_sprite_by_name(
-        &mut self,
-        name: impl AsRef<str>,
-        position: impl Into<Vector2<f32>>,
-        size: impl Into<Vector2<f32>>,
-        color: impl Into<Vector3<f32>>,
-    ) {
-        let texture = self.resources.get_texture(name.as_ref()).unwrap();
-        self.render_sprite(texture, position, size, color)
+    /// Adds a sprite to the batch, drawing the batch first if the sprite is on another page or
+    /// drawn with another shader.
+    fn batch(&mut self, region: Region, params: &DrawParams, program: &'b Program) {
+        if region.page != self.page || !std::ptr::eq(program, self.batch_program) {
+            self.flush();
+            self.page = region.page;
+            self.batch_program = program;
+        }
+
+        let tint = params.tint.into();
+        let vertex = |x: f32, y: f32| {
+            let point = Vector2::new(x, y);
+            Vertex {
+                position: params.to_game(point).into(),
+                tex_coords: params.get_tex_coords(region, point),
+                tint,
+            }
+        };
+        self.batch.extend_from_slice(&[
+            vertex(0.0, 1.0),
+            vertex(1.0, 0.0),
+            vertex(0.0, 0.0),
+            vertex(0.0, 1.0),
+            vertex(1.0, 1.0),
+            vertex(1.0, 0.0),
+        ]);
     }
 
-    pub fn render_sprite(
-        &mut self,
-        texture: &Texture2d,
-        position: impl Into<Vector2<f32>>,
-        size: impl Into<Vector2<f32>>,
-        color: impl Into<Vector3<f32>>,
-    ) {
-        let position = position.into();
-        let size = size.into();
-        let color = color.into();
-
-        #[derive(Copy, Clone)]
-        struct Vertex {
-            position: [f32; 2],
-            tex_coords: [f32; 2],
-        }
+    /// Draws the sprites that have been batched up so far.
+    fn flush(&mut self) {
+        let (texture, settings) = match self.resources.get_page(self.page) {
+            Some(page) if !self.batch.is_empty() => page,
+            _ => return,
+        };
 
-        implement_vertex!(Vertex, position, tex_coords);
-        let vertex_buffer = VertexBuffer::new(
-            self.display,
-            &vec![
-                Vertex {
-                    position: [0.0, 1.0],
-                    tex_coords: [0.0, 1.0],
-                },
-                Vertex {
-                    position: [1.0, 0.0],
-                    tex_coords: [1.0, 0.0],
-                },
-                Vertex {
-                    position: [0.0, 0.0],
-                    tex_coords: [0.0, 0.0],
-                },
-                Vertex {
-                    position: [0.0, 1.0],
-                    tex_coords: [0.0, 1.0],
-                },
-                Vertex {
-                    position: [1.0, 1.0],
-                    tex_coords: [1.0, 1.0],
-                },
-                Vertex {
-                    position: [1.0, 0.0],
-                    tex_coords: [1.0, 0.0],
-                },
-            ],
-        )
-        .unwrap();
+        let vertex_buffer = VertexBuffer::new(self.display, &self.batch).unwrap();
         let indices = NoIndices(PrimitiveType::TrianglesList);
 
-        let projection = glm::ortho::<f32>(0.0, 1024.0, 768.0, 0.0, -1.0, 1.0);
-
-        let mut matrix = Matrix4::<f32>::identity();
-        matrix = matrix.append_nonuniform_scaling(&[size[0], size[1], 1.0].into());
-        matrix = matrix.append_translation(&[position[0], position[1], 0.0].into());
+        let projection =
+            glm::ortho::<f32>(0.0, GAME_WIDTH as f32, GAME_HEIGHT as f32, 0.0, -1.0, 1.0);
 
         let uniforms = uniform! {
-            matrix: *matrix.as_ref(),
             projection: *projection.as_ref(),
-            tint: *color.as_ref(),
-            tex: texture,
+            tex: texture
+                .sampled()
+                .magnify_filter(settings.get_magnify_filter())
+                .minify_filter(settings.get_minify_filter()),
         };
         self.target
             .draw(
                 &vertex_buffer,
-                &indices,
-                &self.program,
+                indices,
+                self.batch_program,
                 &uniforms,
                 &DrawParameters {
                     blend: Blend::alpha_blending(),
+                    viewport: Some(self.viewport.get_rect()),
                     ..Default::default()
                 },
             )
             .unwrap();
+        self.batch.clear();
+    }
+}
+
+impl<'a, 'b> Renderer for SpriteRenderer<'a, 'b> {
+    fn begin_frame(&mut self) {
+        self.queue.clear();
+        self.batch.clear();
+        self.target.clear_color(0.0, 0.0, 0.0, 1.0);
+    }
+
+    /// Draws everything that's been queued. Nothing shows up unless this is called.
+    fn end_frame(&mut self) {
+        self.draw_queued();
+    }
+
+    fn draw_queued(&mut self) {
+        for (region, params, program) in self.queue.drain() {
+            self.batch(region, &params, program);
+        }
+        self.flush();
+    }
+
+    fn get_resources(&self) -> &Resources {
+        self.resources
+    }
+
+    fn get_theme(&self) -> &Theme {
+        self.theme
+    }
+
+    /// Also switches to the theme's shader, if it has one, for whatever is drawn from now on.
+    /// Anything queued before keeps the shader it was queued with.
+    fn set_theme(&mut self, name: &str) {
+        let theme = match self.resources.get_theme(name) {
+            Some(theme) => theme,
+            None => {
+                self.resources.report(format!("no theme named '{}'", name));
+                return;
+            }
+        };
+        let shader = theme.shader.as_deref().unwrap_or("sprite");
+        let program = match self.resources.get_shader(shader) {
+            Ok(program) => program,
+            Err(err) => {
+                self.resources.report(format!("theme '{}': {}", name, err));
+                self.resources.get_shader("sprite").unwrap_or(self.program)
+            }
+        };
+
+        self.theme = theme;
+        self.program = program;
+    }
+
+    fn draw_region(&mut self, region: Region, params: &DrawParams) {
+        self.queue.push(region, params, self.program);
     }
 }
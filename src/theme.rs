@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector3;
+
+use crate::level::BRICK_CODES;
+
+/// The name of the theme that's built into the game and used when no other is chosen.
+pub const DEFAULT_THEME: &str = "classic";
+
+/// The brick colours of the default theme, for brick codes 1 and up.
+const DEFAULT_PALETTE: [[f32; 3]; BRICK_CODES as usize - 1] = [
+    [0.8, 0.8, 0.7],
+    [0.2, 0.6, 1.0],
+    [0.0, 0.7, 0.0],
+    [0.8, 0.8, 0.4],
+    [1.0, 0.5, 0.0],
+];
+
+/// A visual style for the game.
+///
+/// `textures` swaps the textures that sprites are drawn with, by sprite name, including the
+/// `"background"`. Sprites that aren't listed use the texture with the same name. `palette` lists
+/// the brick colours for each brick code starting from 1, falling back to the default colours for
+/// any codes it's missing. `background` tints the background, and `shader` replaces the sprite
+/// shader.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub textures: HashMap<String, String>,
+    pub palette: Vec<[f32; 3]>,
+    pub background: [f32; 3],
+    pub shader: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            textures: HashMap::new(),
+            palette: DEFAULT_PALETTE.to_vec(),
+            background: [1.0, 1.0, 1.0],
+            shader: None,
+        }
+    }
+}
+
+impl Theme {
+    pub fn from_json(data: impl AsRef<str>) -> serde_json::Result<Self> {
+        serde_json::from_str(data.as_ref())
+    }
+
+    /// Returns the name of the texture that draws the sprite called `sprite`.
+    pub fn get_texture<'a>(&'a self, sprite: &'a str) -> &'a str {
+        self.textures
+            .get(sprite)
+            .map(String::as_str)
+            .unwrap_or(sprite)
+    }
+
+    /// Returns the colour of bricks with the given code, or `None` if the code isn't a brick.
+    pub fn get_brick_color(&self, code: u32) -> Option<Vector3<f32>> {
+        if code == 0 || code >= BRICK_CODES {
+            return None;
+        }
+        let index = code as usize - 1;
+        let color = self.palette.get(index).unwrap_or(&DEFAULT_PALETTE[index]);
+        Some((*color).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlisted_sprites_use_their_own_texture() {
+        let theme = Theme::from_json(r#"{"textures": {"paddle": "night_paddle"}}"#).unwrap();
+        assert_eq!(theme.get_texture("paddle"), "night_paddle");
+        assert_eq!(theme.background, [1.0, 1.0, 1.0]);
+        assert_eq!(theme.get_texture("ball"), "ball");
+        assert_eq!(Theme::default().get_texture("background"), "background");
+    }
+
+    #[test]
+    fn short_palettes_fall_back_to_the_default_colours() {
+        let theme = Theme::from_json(r#"{"palette": [[0.1, 0.2, 0.3]]}"#).unwrap();
+        assert_eq!(theme.get_brick_color(1), Some([0.1, 0.2, 0.3].into()));
+        assert_eq!(theme.get_brick_color(2), Some(DEFAULT_PALETTE[1].into()));
+        assert_eq!(theme.get_brick_color(0), None);
+        assert_eq!(theme.get_brick_color(BRICK_CODES), None);
+    }
+
+    #[test]
+    fn shipped_themes_are_valid() {
+        let theme = Theme::from_json(include_str!("../themes/night.json")).unwrap();
+        assert_eq!(theme.palette.len(), DEFAULT_PALETTE.len());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(Theme::from_json(r#"{"colors": []}"#).is_err());
+    }
+}
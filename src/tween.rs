@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::time::Duration;
+
+use nalgebra::{Vector2, Vector3};
+
+/// How a tween speeds up and slows down between its start and end values. Each curve maps the
+/// fraction of time passed to the fraction of the way there, starting at 0 and ending at 1.
+// the standard curves are all here for effects to pick from, though only a few are used yet
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    /// Overshoots the end a little, then settles back onto it.
+    BackOut,
+    /// Overshoots and wobbles around the end like a spring.
+    ElasticOut,
+    /// Bounces off the end a few times before coming to rest on it.
+    BounceOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut if t < 0.5 => 2.0 * t * t,
+            Easing::QuadInOut => 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0,
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::CubicInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+            Easing::SineIn => 1.0 - (t * FRAC_PI_2).cos(),
+            Easing::SineOut => (t * FRAC_PI_2).sin(),
+            Easing::SineInOut => (1.0 - (t * PI).cos()) / 2.0,
+            Easing::BackOut => {
+                let overshoot = 1.70158;
+                let t = t - 1.0;
+                1.0 + (overshoot + 1.0) * t * t * t + overshoot * t * t
+            }
+            Easing::ElasticOut if t == 0.0 || t == 1.0 => t,
+            Easing::ElasticOut => {
+                2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+            }
+            Easing::BounceOut => {
+                let (n, d) = (7.5625, 2.75);
+                if t < 1.0 / d {
+                    n * t * t
+                } else if t < 2.0 / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+/// Values that can be blended between, and so tweened.
+pub trait Lerp: Copy {
+    /// Returns the value `t` of the way from `self` to `to`. `t` can go past 0 and 1 for easing
+    /// curves that overshoot.
+    fn lerp(self, to: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for Vector2<f32> {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for Vector3<f32> {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Step<T> {
+    to: T,
+    duration: Duration,
+    easing: Easing,
+}
+
+/// Animates a value through a chain of steps, each easing from where the last one ended to a new
+/// target.
+///
+/// Tweens only move when they're updated, so driving them from the simulation tick keeps them
+/// deterministic and lets them run headless like the rest of the simulation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tween<T> {
+    /// Where the current step started from.
+    from: T,
+    steps: VecDeque<Step<T>>,
+    /// How far into the current step the tween is.
+    elapsed: Duration,
+}
+
+impl<T: Lerp> Tween<T> {
+    /// A tween resting at `value`, with nothing to play.
+    pub fn new(value: T) -> Self {
+        Tween {
+            from: value,
+            steps: VecDeque::new(),
+            elapsed: Duration::from_millis(0),
+        }
+    }
+
+    /// Adds a step that eases from wherever the tween has got to by then to `to`.
+    pub fn to(mut self, to: T, duration: Duration, easing: Easing) -> Self {
+        self.steps.push_back(Step {
+            to,
+            duration,
+            easing,
+        });
+        self
+    }
+
+    pub fn get_value(&self) -> T {
+        match self.steps.front() {
+            Some(step) => {
+                let t = if step.duration.as_millis() == 0 {
+                    1.0
+                } else {
+                    self.elapsed.as_millis() as f32 / step.duration.as_millis() as f32
+                };
+                self.from.lerp(step.to, step.easing.apply(t))
+            }
+            None => self.from,
+        }
+    }
+
+    // the game reacts to `update` reporting completion instead of asking
+    #[allow(dead_code)]
+    pub fn is_finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Moves the tween on by `delta`, carrying any time left over from one step into the next.
+    /// Returns `true` on the update that finishes the last step, and only that update, so the
+    /// caller can react to the tween completing.
+    pub fn update(&mut self, delta: Duration) -> bool {
+        if self.steps.is_empty() {
+            return false;
+        }
+        self.elapsed += delta;
+        while let Some(step) = self.steps.front() {
+            if self.elapsed < step.duration {
+                return false;
+            }
+            self.elapsed -= step.duration;
+            self.from = step.to;
+            self.steps.pop_front();
+        }
+        self.elapsed = Duration::from_millis(0);
+        true
+    }
+
+    /// Stops the tween where it is. A cancelled tween never reports that it completed.
+    // nothing interrupts a tween partway through yet
+    #[allow(dead_code)]
+    pub fn cancel(&mut self) {
+        self.from = self.get_value();
+        self.steps.clear();
+        self.elapsed = Duration::from_millis(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 13] = [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::SineIn,
+        Easing::SineOut,
+        Easing::SineInOut,
+        Easing::BackOut,
+        Easing::ElasticOut,
+        Easing::BounceOut,
+    ];
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn easings_start_at_0_and_end_at_1() {
+        for easing in EASINGS.iter() {
+            assert_close(easing.apply(0.0), 0.0);
+            assert_close(easing.apply(1.0), 1.0);
+            assert_close(easing.apply(-1.0), 0.0);
+            assert_close(easing.apply(2.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn easings_have_the_right_shape() {
+        assert_close(Easing::Linear.apply(0.25), 0.25);
+        assert_close(Easing::QuadIn.apply(0.5), 0.25);
+        assert_close(Easing::QuadOut.apply(0.5), 0.75);
+        assert_close(Easing::CubicInOut.apply(0.5), 0.5);
+        assert_close(Easing::SineInOut.apply(0.5), 0.5);
+        assert!(Easing::CubicIn.apply(0.5) < Easing::QuadIn.apply(0.5));
+        assert!((0..100).any(|i| Easing::BackOut.apply(i as f32 / 100.0) > 1.0));
+        assert!((0..100).any(|i| Easing::ElasticOut.apply(i as f32 / 100.0) > 1.0));
+        assert!((0..=100).all(|i| Easing::BounceOut.apply(i as f32 / 100.0) <= 1.0 + 1e-6));
+    }
+
+    #[test]
+    fn chained_steps_follow_on_from_each_other() {
+        let mut tween = Tween::new(0.0)
+            .to(10.0, ms(100), Easing::Linear)
+            .to(10.0, ms(50), Easing::Linear)
+            .to(0.0, ms(100), Easing::Linear);
+
+        let mut values = Vec::new();
+        for _ in 0..6 {
+            tween.update(ms(50));
+            values.push(tween.get_value());
+        }
+        assert_eq!(values, [5.0, 10.0, 10.0, 5.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn completion_is_reported_once() {
+        let mut tween = Tween::new(1.0).to(2.0, ms(30), Easing::QuadOut);
+        let completed: Vec<_> = (0..5).map(|_| tween.update(ms(10))).collect();
+        assert_eq!(completed, [false, false, true, false, false]);
+        assert!(tween.is_finished());
+        assert_eq!(tween.get_value(), 2.0);
+
+        // leftover time carries through steps that take no time at all
+        let mut tween =
+            Tween::new(0.0)
+                .to(1.0, ms(0), Easing::Linear)
+                .to(3.0, ms(20), Easing::Linear);
+        assert!(!tween.update(ms(10)));
+        assert_eq!(tween.get_value(), 2.0);
+    }
+
+    #[test]
+    fn cancelling_holds_the_current_value() {
+        let mut tween = Tween::new(0.0).to(100.0, ms(100), Easing::Linear);
+        tween.update(ms(40));
+        tween.cancel();
+        assert!(tween.is_finished());
+        assert_eq!(tween.get_value(), 40.0);
+        assert!(!tween.update(ms(100)));
+        assert_eq!(tween.get_value(), 40.0);
+    }
+
+    #[test]
+    fn tweens_vectors_componentwise() {
+        let mut tween = Tween::new(Vector2::new(0.0, 10.0)).to(
+            Vector2::new(10.0, 0.0),
+            ms(100),
+            Easing::Linear,
+        );
+        tween.update(ms(25));
+        assert_eq!(tween.get_value(), Vector2::new(2.5, 7.5));
+
+        let mut tween =
+            Tween::new(Vector3::zeros()).to(Vector3::new(1.0, 2.0, 4.0), ms(10), Easing::QuadIn);
+        tween.update(ms(5));
+        assert_eq!(tween.get_value(), Vector3::new(0.25, 0.5, 1.0));
+    }
+}
@@ -0,0 +1,65 @@
+use glium::Rect;
+use nalgebra::Vector2;
+
+use crate::{GAME_HEIGHT, GAME_WIDTH};
+
+/// The region of the window that the game is drawn into.
+///
+/// The game is always rendered at `GAME_WIDTH`x`GAME_HEIGHT` logical units and scaled to fit the
+/// window, with black bars on the sides (pillarbox) or top and bottom (letterbox) when the aspect
+/// ratios don't match. All measurements are in physical pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+    window_height: u32,
+}
+
+impl Viewport {
+    pub fn fit(dimensions: (u32, u32)) -> Self {
+        let (window_width, window_height) = dimensions;
+        let scale = (window_width as f32 / GAME_WIDTH as f32)
+            .min(window_height as f32 / GAME_HEIGHT as f32);
+        let width = ((GAME_WIDTH as f32 * scale).round() as u32).min(window_width);
+        let height = ((GAME_HEIGHT as f32 * scale).round() as u32).min(window_height);
+        Viewport {
+            left: (window_width - width) / 2,
+            top: (window_height - height) / 2,
+            width,
+            height,
+            window_height,
+        }
+    }
+
+    pub fn get_rect(&self) -> Rect {
+        // glium measures from the bottom-left corner of the window
+        Rect {
+            left: self.left,
+            bottom: self.window_height - self.top - self.height,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    pub fn get_scale(&self) -> f32 {
+        self.width as f32 / GAME_WIDTH as f32
+    }
+
+    /// Maps a point in physical window pixels (origin at the top-left) into game space.
+    ///
+    /// Points in the letterbox bars map to coordinates outside of the game area.
+    pub fn to_game(self, position: impl Into<Vector2<f32>>) -> Vector2<f32> {
+        let position = position.into();
+        let scale = self.get_scale();
+        if scale <= 0.0 {
+            return Vector2::zeros();
+        }
+        [
+            (position[0] - self.left as f32) / scale,
+            (position[1] - self.top as f32) / scale,
+        ]
+        .into()
+    }
+}